@@ -79,6 +79,31 @@ impl Coord {
         }
     }
 
+    // the number of steps it would take to walk from self
+    // to other, assuming diagonal movement is allowed
+    pub fn distance(&self, other: &Coord) -> usize {
+        let dx = if self.x > other.x { self.x - other.x } else { other.x - self.x };
+        let dy = if self.y > other.y { self.y - other.y } else { other.y - self.y };
+        std::cmp::max(dx, dy)
+    }
+
+    // the direction one would need to step in to get
+    // closer to other. returns None if self == other.
+    pub fn direction_to(&self, other: &Coord) -> Option<Direction> {
+        use std::cmp::Ordering::*;
+        match (other.y.cmp(&self.y), other.x.cmp(&self.x)) {
+            (Less, Less)       => Some(Direction::NorthWest),
+            (Less, Equal)      => Some(Direction::North),
+            (Less, Greater)    => Some(Direction::NorthEast),
+            (Equal, Less)      => Some(Direction::West),
+            (Equal, Equal)     => None,
+            (Equal, Greater)   => Some(Direction::East),
+            (Greater, Less)    => Some(Direction::SouthWest),
+            (Greater, Equal)   => Some(Direction::South),
+            (Greater, Greater) => Some(Direction::SouthEast),
+        }
+    }
+
     pub fn clamp_x(mut self, max: usize) -> Coord {
         let newx = match () {
             _ if self.x < max => self.x,
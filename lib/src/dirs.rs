@@ -2,8 +2,9 @@ use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
+use serde::{Serialize, Deserialize};
 
-#[derive(Copy, Debug, Clone, PartialEq)]
+#[derive(Copy, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     South,
@@ -15,6 +16,34 @@ pub enum Direction {
     SouthEast,
 }
 
+impl Direction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+            Direction::NorthWest => "northwest",
+            Direction::NorthEast => "northeast",
+            Direction::SouthWest => "southwest",
+            Direction::SouthEast => "southeast",
+        }
+    }
+
+    pub fn all() -> [Direction; 8] {
+        [
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+            Direction::NorthWest,
+            Direction::NorthEast,
+            Direction::SouthWest,
+            Direction::SouthEast,
+        ]
+    }
+}
+
 impl Distribution<Direction> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Direction {
         match rng.gen_range(0, 7) {
@@ -1,5 +1,6 @@
 use crate::dun_s1::*;
 use crate::features::*;
+use crate::items::*;
 use serde::{Serialize, Deserialize};
 use std::vec::Vec;
 
@@ -8,8 +9,7 @@ pub struct DungeonTile {
     pub tiletype: TileType,
     pub tile_material: String,
 
-    // TODO: define Item structs
-    pub items: Vec<u8>,
+    pub items: Vec<Item>,
     pub mobs: Option<u64>, // mob id
 
     // in kelvin, of course
@@ -21,9 +21,18 @@ pub struct DungeonTile {
 // including items, mobs, material, etc
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DungeonS2 {
+    // e.g. "the Mines of Angband"; may be empty
+    #[serde(default)]
+    pub name: String,
+
     pub d: Vec<Vec<DungeonTile>>,
     pub width: usize, pub height: usize,
     pub features: Vec<Feature>,
+
+    // the game time at which the mobs on this level were
+    // last simulated
+    #[serde(default)]
+    pub last_simulated: u64,
 }
 
 impl DungeonS2 {
@@ -46,9 +55,43 @@ impl DungeonS2 {
         }
 
         DungeonS2 {
+            name: String::new(),
             d: dungeon,
             width: dg.width, height: dg.height,
             features: dg.features.clone(),
+            last_simulated: 0,
+        }
+    }
+
+    // find the floor tile closest to coords that doesn't
+    // already have a mob on it
+    pub fn nearest_free_floor(&self, coords: (usize, usize)) -> Option<(usize, usize)> {
+        let cy = std::cmp::min(coords.0, self.height - 1);
+        let cx = std::cmp::min(coords.1, self.width - 1);
+        let max_radius = std::cmp::max(self.width, self.height);
+
+        for radius in 0..max_radius {
+            let starty = cy.saturating_sub(radius);
+            let startx = cx.saturating_sub(radius);
+            let endy = std::cmp::min(cy + radius, self.height - 1);
+            let endx = std::cmp::min(cx + radius, self.width - 1);
+
+            for y in starty..=endy {
+                for x in startx..=endx {
+                    // only look at the edge of the square; the
+                    // inside has already been checked
+                    if y != starty && y != endy && x != startx && x != endx {
+                        continue;
+                    }
+
+                    let tile = &self.d[y][x];
+                    if tile.tiletype == TileType::Floor && tile.mobs.is_none() {
+                        return Some((y, x));
+                    }
+                }
+            }
         }
+
+        None
     }
 }
@@ -1,12 +1,23 @@
 use chrono::prelude::*;
 use crate::dun_s1::*;
 use crate::dun_s2::*;
+use crate::items::*;
+use crate::message::*;
 use crate::player::*;
 use crate::mob::*;
+use crate::utils;
 use rand::prelude::*;
 use serde::{Serialize, Deserialize};
 use std::vec::Vec;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// how much game time passes in a single turn
+pub const TIME_PER_TURN: u64 = 100;
+
+// how many turns make up a year, for the purpose of aging mobs
+pub const TURNS_PER_YEAR: u64 = 10000;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Dungeon {
@@ -15,6 +26,25 @@ pub struct Dungeon {
     pub levels: Vec<DungeonS2>,
     pub player: Player,
     pub mobs: HashMap<u64, Mob>,
+
+    // the current game time (see TIME_PER_TURN)
+    #[serde(default)]
+    pub time: u64,
+
+    // the seed the world was generated from, if known
+    #[serde(default)]
+    pub seed: u64,
+
+    // everything the player has been told so far
+    #[serde(default)]
+    pub messages: MessageLog,
+
+    // where each mob is, as mob id => (level, (y, x)), so that the
+    // levels don't have to be scanned for them every turn. it's kept
+    // up to date by move_mob, kill_mob, and spawn_mob, and isn't
+    // saved; index_mobs builds it again when a dungeon is loaded.
+    #[serde(skip)]
+    positions: HashMap<u64, (usize, (usize, usize))>,
 }
 
 impl Dungeon {
@@ -25,13 +55,19 @@ impl Dungeon {
         R: Rng
     {
         let player = Player::new(&mut lvls[0], &mut mobs, 0, rng, player_template);
-        Dungeon {
+        let mut dungeon = Dungeon {
             world_name: name,
             created_on: Local::now().timestamp(),
             levels: lvls.to_vec(),
             mobs: mobs,
             player: player,
-        }
+            time: 0,
+            seed: 0,
+            messages: MessageLog::default(),
+            positions: HashMap::new(),
+        };
+        dungeon.index_mobs();
+        dungeon
     }
 
     pub fn move_mob(
@@ -54,17 +90,129 @@ impl Dungeon {
 
                 self.levels[oldlevel].d[old_pos.0][old_pos.1].mobs = Some(othermod);
                 self.levels[newlevel].d[new_pos.0][new_pos.1].mobs = Some(mob);
+                self.positions.insert(othermod, (oldlevel, old_pos));
             } else {
                 self.levels[newlevel].d[new_pos.0][new_pos.1].mobs = Some(mob);
                 self.levels[oldlevel].d[old_pos.0][old_pos.1].mobs = None;
             }
+            self.positions.insert(mob, (newlevel, new_pos));
             Ok(())
         } else {
             Err(())
         }
     }
 
+    // find where every mob in the dungeon is
+    // returns a table of mob id => (level, (y, x))
+    pub fn mob_positions(&self) -> HashMap<u64, (usize, (usize, usize))> {
+        self.positions.clone()
+    }
+
+    pub fn mob_position(&self, mob_id: u64) -> Option<(usize, (usize, usize))> {
+        self.positions.get(&mob_id).copied()
+    }
+
+    // find every mob by looking through all the levels. only
+    // needed when the tiles have been changed behind our back,
+    // e.g. by loading a dungeon or generating one.
+    pub fn index_mobs(&mut self) {
+        self.positions.clear();
+        for lvl in 0..self.levels.len() {
+            for y in 0..self.levels[lvl].height {
+                for x in 0..self.levels[lvl].width {
+                    if let Some(mob_id) = self.levels[lvl].d[y][x].mobs {
+                        self.positions.insert(mob_id, (lvl, (y, x)));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn turn(&self) -> u64 {
+        self.time / TIME_PER_TURN
+    }
+
+    // remove a mob from the dungeon, leaving its corpse behind
+    pub fn kill_mob(&mut self, level: usize, coords: (usize, usize),
+        template: &MobTemplate) -> Option<Mob>
+    {
+        let tile = &mut self.levels[level].d[coords.0][coords.1];
+        let mob_id = tile.mobs.take()?;
+        let mob = self.mobs.remove(&mob_id)?;
+        tile.items.push(Item::corpse(&mob, template));
+        self.positions.remove(&mob_id);
+        Some(mob)
+    }
+
+    // put a new mob into the dungeon, as close to coords as
+    // possible. returns the new mob's id and where it ended up.
+    pub fn spawn_mob(&mut self, level: usize, coords: (usize, usize), mob: Mob)
+        -> Option<(u64, (usize, usize))>
+    {
+        let coords = self.levels[level].nearest_free_floor(coords)?;
+        let mut mob_id = utils::calculate_hash(&mob);
+        while self.mobs.contains_key(&mob_id) {
+            mob_id = mob_id.wrapping_add(1);
+        }
+
+        self.levels[level].d[coords.0][coords.1].mobs = Some(mob_id);
+        self.mobs.insert(mob_id, mob);
+        self.positions.insert(mob_id, (level, coords));
+        Some((mob_id, coords))
+    }
+
+    // the mobs that were summoned or raised by a mob
+    pub fn minions_of(&self, master: u64) -> Vec<u64> {
+        self.mobs.iter()
+            .filter(|(_, m)| m.master == Some(master))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn player_mob_id(&self) -> u64 {
+        self.at(self.player.level, self.player.coords).mobs.unwrap()
+    }
+
     pub fn at(&self, level: usize, coords: (usize, usize)) -> DungeonTile {
         self.levels[level].d[coords.0][coords.1].clone()
     }
+
+    // a hash of the parts of the dungeon that change as the game's
+    // played, for telling whether two games have gone the same way.
+    // it doesn't depend on the order the mobs are stored in, but it
+    // may well change between builds of the game.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.time.hash(&mut hasher);
+        self.player.level.hash(&mut hasher);
+        self.player.coords.hash(&mut hasher);
+
+        let mut ids = self.mobs.keys().collect::<Vec<&u64>>();
+        ids.sort();
+        for id in ids {
+            let mob = &self.mobs[id];
+            id.hash(&mut hasher);
+            mob.hash(&mut hasher);
+            mob.current_mode.hash(&mut hasher);
+            (mob.strength, mob.agility, mob.endurance, mob.metabolism,
+                mob.willpower, mob.focus, mob.intelligence).hash(&mut hasher);
+            (mob.hunger, mob.thirst, mob.tiredness, mob.age_turns)
+                .hash(&mut hasher);
+            (mob.blood_lost, mob.bleeding, mob.confused, mob.suspicion,
+                mob.alerted).hash(&mut hasher);
+            mob.master.hash(&mut hasher);
+            mob.inventory.len().hash(&mut hasher);
+        }
+
+        for level in &self.levels {
+            for row in &level.d {
+                for tile in row {
+                    tile.mobs.hash(&mut hasher);
+                    tile.items.len().hash(&mut hasher);
+                }
+            }
+        }
+
+        hasher.finish()
+    }
 }
@@ -0,0 +1,144 @@
+// the ways a dungeon (or a save) can be written out
+//
+// RON is easy to read and edit by hand, but slow and huge for a big
+// world with every tile's material spelled out. the binary encoding
+// (bincode) is much smaller and quicker to load, and can be compressed
+// on top of that. binary files begin with a few magic bytes, so that
+// whatever loads them can tell which encoding was used.
+
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::io::Read;
+
+const BINARY_MAGIC: &[u8] = b"thbin\0";
+const COMPRESSED_MAGIC: &[u8] = b"thzip\0";
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    Ron,
+    Binary,
+
+    // binary, compressed with zlib
+    Compressed,
+}
+
+impl Encoding {
+    // work out how some data was encoded. anything
+    // without the magic bytes is taken to be RON.
+    pub fn detect(data: &[u8]) -> Encoding {
+        if data.starts_with(BINARY_MAGIC) {
+            Encoding::Binary
+        } else if data.starts_with(COMPRESSED_MAGIC) {
+            Encoding::Compressed
+        } else {
+            Encoding::Ron
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name {
+            "ron" => Some(Encoding::Ron),
+            "binary" => Some(Encoding::Binary),
+            "compressed" => Some(Encoding::Compressed),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Ron => "ron",
+            Encoding::Binary => "thb",
+            Encoding::Compressed => "thz",
+        }
+    }
+}
+
+pub fn encode<T>(value: &T, encoding: Encoding) -> Result<Vec<u8>, String>
+where
+    T: Serialize
+{
+    match encoding {
+        Encoding::Ron => ron::to_string(value)
+            .map(|s| s.into_bytes())
+            .map_err(|e| e.to_string()),
+        Encoding::Binary => {
+            let mut data = BINARY_MAGIC.to_vec();
+            bincode::serialize_into(&mut data, value)
+                .map_err(|e| e.to_string())?;
+            Ok(data)
+        },
+        Encoding::Compressed => {
+            let mut encoder = ZlibEncoder::new(COMPRESSED_MAGIC.to_vec(),
+                Compression::default());
+            bincode::serialize_into(&mut encoder, value)
+                .map_err(|e| e.to_string())?;
+            encoder.finish().map_err(|e| e.to_string())
+        },
+    }
+}
+
+// decode some data, in whichever encoding it's in
+//
+// a binary value can be decoded from data that carries on
+// past its end, so a struct that's the first part of another
+// can be read on its own.
+pub fn decode<T>(data: &[u8]) -> Result<T, String>
+where
+    T: DeserializeOwned
+{
+    match Encoding::detect(data) {
+        Encoding::Ron => {
+            let text = std::str::from_utf8(data)
+                .map_err(|e| e.to_string())?;
+            ron::de::from_str(text).map_err(|e| e.to_string())
+        },
+        Encoding::Binary => bincode::deserialize(&data[BINARY_MAGIC.len()..])
+            .map_err(|e| e.to_string()),
+        Encoding::Compressed => {
+            let mut decoded = Vec::new();
+            ZlibDecoder::new(&data[COMPRESSED_MAGIC.len()..])
+                .read_to_end(&mut decoded)
+                .map_err(|e| e.to_string())?;
+            bincode::deserialize(&decoded).map_err(|e| e.to_string())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Thing {
+        name: String,
+        sizes: Vec<u32>,
+        seed: u64,
+    }
+
+    fn thing() -> Thing {
+        Thing {
+            name: "thing".to_string(),
+            sizes: vec![1, 2, 3, 500],
+            seed: 42,
+        }
+    }
+
+    #[test]
+    fn round_trip_in_each_encoding() {
+        for &encoding in &[Encoding::Ron, Encoding::Binary, Encoding::Compressed] {
+            let data = encode(&thing(), encoding).unwrap();
+            assert_eq!(Encoding::detect(&data), encoding);
+            assert_eq!(decode::<Thing>(&data).unwrap(), thing());
+        }
+    }
+
+    #[test]
+    fn unknown_magic_is_taken_to_be_ron() {
+        let data = b"thxyz\0not really anything";
+        assert_eq!(Encoding::detect(data), Encoding::Ron);
+        assert!(decode::<Thing>(data).is_err());
+    }
+}
@@ -1,4 +1,7 @@
+use crate::material::*;
+use crate::mob::*;
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum ItemType {
@@ -7,4 +10,85 @@ pub enum ItemType {
     Shoes, Headwear,
     Clothing,
     DiggingTool,
+    Corpse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Item {
+    // e.g. "iron pickaxe"
+    pub name: String,
+
+    pub item_type: ItemType,
+
+    // what material is it made of
+    pub material: String,
+
+    pub glyph: char,
+
+    // in grams
+    pub weight: usize,
+
+    // for corpses, the template of the mob that died
+    #[serde(default)]
+    pub mob_template: Option<String>,
+}
+
+impl Item {
+    pub fn corpse(mob: &Mob, template: &MobTemplate) -> Item {
+        Item {
+            name: format!("{} corpse", template.short_name),
+            item_type: ItemType::Corpse,
+            material: template.composition.clone(),
+            glyph: '%',
+            weight: mob.weight as usize * 1000,
+            mob_template: Some(mob.from_mob_template.clone()),
+        }
+    }
+
+    pub fn is_edible(&self, materials: &HashMap<String, MaterialInfo>) -> bool {
+        materials.get(&self.material).map(|m| m.edible).unwrap_or(false)
+    }
+
+    pub fn is_drinkable(&self, materials: &HashMap<String, MaterialInfo>) -> bool {
+        materials.get(&self.material).map(|m| m.drinkable).unwrap_or(false)
+    }
+}
+
+// the places a mob can wear or wield an item
+// these depend on what body parts the mob has; a mob with
+// no hands can't wield a weapon, for example.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Hand(usize),
+    Head,
+    Feet,
+    Armor,
+    Clothing,
+}
+
+impl EquipmentSlot {
+    pub fn accepts(&self, item_type: ItemType) -> bool {
+        match self {
+            EquipmentSlot::Hand(_) => match item_type {
+                ItemType::Weapon
+                | ItemType::DiggingTool
+                | ItemType::Block => true,
+                _ => false,
+            },
+            EquipmentSlot::Head => item_type == ItemType::Headwear,
+            EquipmentSlot::Feet => item_type == ItemType::Shoes,
+            EquipmentSlot::Armor => item_type == ItemType::Armor,
+            EquipmentSlot::Clothing => item_type == ItemType::Clothing,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            EquipmentSlot::Hand(i) => format!("hand {}", i + 1),
+            EquipmentSlot::Head => "head".to_string(),
+            EquipmentSlot::Feet => "feet".to_string(),
+            EquipmentSlot::Armor => "armor".to_string(),
+            EquipmentSlot::Clothing => "clothing".to_string(),
+        }
+    }
 }
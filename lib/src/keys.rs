@@ -0,0 +1,212 @@
+// keys (and mouse buttons), and keybindings loaded from files
+//
+// nothing here knows about termbox, so that keybindings can be written
+// down in RON; th and mv each turn termbox's key codes into Keys. each
+// binary has its own set of actions, so Keybindings is generic over
+// them.
+
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Key {
+    Char(char),
+
+    // control and a letter
+    Ctrl(char),
+
+    // the function keys, F(1) to F(12)
+    F(u8),
+
+    Up,
+    Down,
+    Left,
+    Right,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Space,
+}
+
+impl Key {
+    pub fn name(&self) -> String {
+        match self {
+            Key::Char(c) => c.to_string(),
+            Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+            Key::F(n) => format!("F{}", n),
+            Key::Up => "Up".to_string(),
+            Key::Down => "Down".to_string(),
+            Key::Left => "Left".to_string(),
+            Key::Right => "Right".to_string(),
+            Key::Insert => "Ins".to_string(),
+            Key::Delete => "Del".to_string(),
+            Key::Home => "Home".to_string(),
+            Key::End => "End".to_string(),
+            Key::PageUp => "PgUp".to_string(),
+            Key::PageDown => "PgDn".to_string(),
+            Key::Escape => "Esc".to_string(),
+            Key::Enter => "Enter".to_string(),
+            Key::Tab => "Tab".to_string(),
+            Key::Backspace => "Backspace".to_string(),
+            Key::Space => "Space".to_string(),
+        }
+    }
+}
+
+// a key, along with whatever modifiers were held down
+// (termbox can only tell us about alt)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyPress {
+    pub key: Key,
+    pub alt: bool,
+}
+
+impl KeyPress {
+    pub fn name(&self) -> String {
+        if self.alt {
+            format!("Alt-{}", self.key.name())
+        } else {
+            self.key.name()
+        }
+    }
+}
+
+// which mouse button was pressed, or which way
+// the wheel was turned
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Release,
+    WheelUp,
+    WheelDown,
+}
+
+// what every binary's actions need to be, so that they
+// can be bound to keys
+pub trait Action: Copy + PartialEq + DeserializeOwned {
+    // what the action does, for the help screen
+    fn description(&self) -> String;
+}
+
+// one line of a keybinding file, e.g.
+// (key: Ctrl('s'), action: Save)
+#[derive(Clone, Debug, Deserialize)]
+pub struct Keybinding<A> {
+    pub key: Key,
+    #[serde(default)]
+    pub alt: bool,
+    pub action: A,
+}
+
+impl<A> Keybinding<A> {
+    pub fn trigger(&self) -> KeyPress {
+        KeyPress { key: self.key, alt: self.alt }
+    }
+}
+
+pub struct Keybindings<A>(Vec<Keybinding<A>>);
+
+impl<A> Keybindings<A>
+where
+    A: Action
+{
+    // load the keybindings for a program from the user's config
+    // directory, or the defaults in dat if they don't have any
+    pub fn load(program: &str, default_path: &str) -> Result<Keybindings<A>, String> {
+        let path = match config_path(program, "keys.ron") {
+            Some(p) if p.exists() => p,
+            _ => PathBuf::from(default_path),
+        };
+
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let bindings: Vec<Keybinding<A>> = ron::de::from_str(&data)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let keybindings = Keybindings(bindings);
+
+        let conflicts = keybindings.conflicts();
+        if !conflicts.is_empty() {
+            return Err(format!("{}: {}", path.display(), conflicts.join("; ")));
+        }
+
+        Ok(keybindings)
+    }
+
+    // keys that are bound to more than one action
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut seen: HashMap<KeyPress, A> = HashMap::new();
+        let mut conflicts = Vec::new();
+
+        for binding in &self.0 {
+            match seen.get(&binding.trigger()) {
+                Some(action) if *action != binding.action => {
+                    conflicts.push(format!("{} is bound to both \"{}\" and \"{}\"",
+                        binding.trigger().name(), action.description(),
+                        binding.action.description()));
+                },
+                Some(_) => (),
+                None => {
+                    seen.insert(binding.trigger(), binding.action);
+                },
+            }
+        }
+
+        conflicts
+    }
+
+    pub fn as_table(&self) -> HashMap<KeyPress, A> {
+        self.0.iter()
+            .map(|b| (b.trigger(), b.action))
+            .collect()
+    }
+
+    // the keys bound to an action
+    pub fn keys_for(&self, action: A) -> Vec<KeyPress> {
+        self.0.iter()
+            .filter(|b| b.action == action)
+            .map(|b| b.trigger())
+            .collect()
+    }
+
+    // every action that has a key, along with its keys,
+    // in the order they were bound in
+    pub fn help(&self) -> Vec<(String, String)> {
+        let mut actions: Vec<A> = Vec::new();
+        for binding in &self.0 {
+            if !actions.contains(&binding.action) {
+                actions.push(binding.action);
+            }
+        }
+
+        actions.iter()
+            .map(|a| {
+                let keys = self.keys_for(*a).iter()
+                    .map(|k| k.name())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                (keys, a.description())
+            })
+            .collect()
+    }
+}
+
+// where a program keeps its configuration, i.e.
+// $XDG_CONFIG_HOME/<program>/<file> or ~/.config/<program>/<file>
+pub fn config_path(program: &str, file: &str) -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(d) => PathBuf::from(d),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join(program).join(file))
+}
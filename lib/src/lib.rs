@@ -4,15 +4,24 @@ pub mod dirs;
 pub mod dun_s1;
 pub mod dun_s2;
 pub mod dungeon;
+pub mod encoding;
 pub mod features;
 pub mod id;
 pub mod info_files;
 pub mod items;
+pub mod keys;
 pub mod material;
+pub mod message;
 pub mod math;
 pub mod mob;
+pub mod palette;
+pub mod path;
 pub mod player;
 pub mod priority;
 pub mod rect;
+pub mod render;
+pub mod save;
+#[cfg(feature = "termbox")]
+pub mod termbox;
 pub mod utils;
 pub mod value;
@@ -50,6 +50,11 @@ pub struct MaterialInfo {
     pub color_fg: Color,
     pub block_glyph: char,
 
+    // what's drawn instead of block_glyph when
+    // only ASCII can be shown
+    #[serde(default = "default_ascii_block_glyph")]
+    pub ascii_block_glyph: char,
+
     // all temperatures are in kelvin
     pub melting_point: f64,
 
@@ -72,6 +77,13 @@ pub struct MaterialInfo {
     pub occurs_naturally: Option<Vec<ItemType>>,
 
     pub edible: bool,
+
+    #[serde(default)]
+    pub drinkable: bool,
+}
+
+fn default_ascii_block_glyph() -> char {
+    '#'
 }
 
 impl Id for MaterialInfo {
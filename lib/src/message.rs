@@ -0,0 +1,78 @@
+use crate::priority::*;
+use serde::{Serialize, Deserialize};
+
+// how many messages are kept in the log before
+// the oldest ones are forgotten
+const MAX_MESSAGES: usize = 1000;
+
+// a game message
+// e.g. "the kobold strikes you with her lead mace!"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub priority: Priority,
+
+    // how many times in a row the message was given
+    pub count: usize,
+}
+
+impl Message {
+    // TODO: msg!() macro?
+    pub fn new(s: &str, p: Priority) -> Message {
+        Message {
+            text: s.to_string(),
+            priority: p,
+            count: 1,
+        }
+    }
+
+    // the text, with a note of how many times it was repeated
+    // e.g. "The troll misses you. (x3)"
+    pub fn full_text(&self) -> String {
+        if self.count > 1 {
+            format!("{} (x{})", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+// every message the player has been given, oldest first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageLog(Vec<Message>);
+
+impl MessageLog {
+    // add a message, or if it's the same as the last
+    // one, just count it again
+    pub fn push(&mut self, message: Message) {
+        if let Some(last) = self.0.last_mut() {
+            if last.text == message.text {
+                last.count += 1;
+                last.priority = message.priority;
+                return;
+            }
+        }
+
+        self.0.push(message);
+        if self.0.len() > MAX_MESSAGES {
+            self.0.remove(0);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn all(&self) -> &[Message] {
+        &self.0
+    }
+
+    // the last n messages
+    pub fn recent(&self, n: usize) -> &[Message] {
+        &self.0[self.0.len().saturating_sub(n)..]
+    }
+}
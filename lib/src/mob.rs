@@ -1,13 +1,77 @@
 use crate::colors::*;
 use crate::coord::*;
 use crate::id::*;
+use crate::items::*;
 use crate::value::*;
 use crate::dun_s2::*;
+use crate::dungeon::*;
 use rand::prelude::*;
 use serde::{Serialize, Deserialize};
 use std::hash::{Hash, Hasher};
 use std::collections::HashMap;
 
+// how many grams a mob can carry for each point of strength
+const CARRY_WEIGHT_PER_STRENGTH: usize = 2000;
+
+// needs (hunger, thirst, tiredness) go from 0 (completely
+// satisfied) to NEED_MAX (starving, parched, exhausted)
+pub const NEED_MAX: u16 = 10000;
+
+// past this point, a mob will go out of its way to satisfy a need,
+// and its stats will start to suffer
+pub const NEED_URGENT: u16 = NEED_MAX / 2;
+
+// how much tiredness a mob gains every turn it's awake,
+// and how much it loses every turn it's asleep
+const TIREDNESS_RATE: u64 = 4;
+const SLEEP_RECOVERY: u64 = 40;
+
+// how much hunger (or thirst) each gram of food
+// (or drink) takes away
+const NEED_PER_GRAM: u64 = 10;
+
+// no matter how hungry a mob gets, its stats won't
+// drop below this percentage
+const MIN_STAT_PERCENTAGE: u16 = 20;
+
+// millilitres of blood per kilogram of body weight,
+// not counting the extra blood that endurance gives
+const BLOOD_PER_KG: u32 = 50;
+
+// millilitres of blood regenerated every turn for
+// each point of metabolism
+const BLOOD_REGEN: u64 = 1;
+
+// millilitres of blood a vampire's body uses up every turn
+const VAMPIRE_BLOOD_USE: u64 = 2;
+
+// as a percentage of max blood: below FAINT_BLOOD the mob faints,
+// and it comes to again once it's back up to RECOVER_BLOOD
+const FAINT_BLOOD: u32 = 40;
+const RECOVER_BLOOD: u32 = 50;
+
+// below this percentage of max blood, vampires go looking for
+// someone to feed on
+pub const VAMPIRE_HUNGRY_BLOOD: u32 = 70;
+
+// how many turns a mob stays confused after coming to
+const FAINT_CONFUSION: u64 = 30;
+
+// how many turns a mob holds a grudge against
+// another mob that attacked it
+const GRUDGE_TURNS: u64 = 1000;
+
+// how much suspicion it takes for a mob to become
+// suspicious of the player, and then sure of where they are
+pub const SUSPICIOUS_AT: u16 = 30;
+pub const ALERTED_AT: u16 = 100;
+
+// how far an alerted mob's suspicion has to fall before it stops
+// being sure of where the player is. it's well below ALERTED_AT, so
+// that losing sight of the player for a moment doesn't make the mob
+// notice them all over again when it sees them next.
+pub const CALM_AT: u16 = 60;
+
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MobBody {
     Eye,
@@ -105,6 +169,13 @@ pub struct MobTemplate {
     pub glyph_fg: Option<Color>,
 
     pub alignment: MobAlignment,
+
+    // mobs of the same faction won't fight each other,
+    // whatever their alignment
+    // e.g. "trolls"
+    #[serde(default)]
+    pub faction: Option<String>,
+
     pub height: Value<u16>, // centimeters
     pub width: Value<u16>, // centimeters also
     pub weight: Value<u16>, // kilograms
@@ -162,6 +233,11 @@ pub struct MobTemplate {
     pub age: Value<u64>,
     pub max_age: Option<Value<u64>>, // demons don't die of old age
 
+    // instead of dying when it reaches max_age, the mob
+    // will turn into a mob of this template
+    #[serde(default)]
+    pub matures_into: Option<String>,
+
     // most demons will have this
     // controls whether character will be able to summon other demons
     // to assist them
@@ -230,6 +306,7 @@ impl MobTemplate {
             unicode_glyph: self.unicode_glyph,
             glyph_fg: self.glyph_fg,
             alignment: self.alignment,
+            faction: self.faction.clone(),
             height: self.height.get(rng),
             width: self.width.get(rng),
             weight: self.weight.get(rng),
@@ -263,21 +340,154 @@ impl MobTemplate {
 
             fov: Vec::new(),
             memory: HashMap::new(),
+            sightings: HashMap::new(),
+            other_levels_memory: HashMap::new(),
+
+            inventory: Vec::new(),
+            equipment: HashMap::new(),
+
+            hunger: 0,
+            thirst: 0,
+            tiredness: 0,
+            age_turns: 0,
+
+            master: None,
+            max_raised: self.necromancer.get(rng),
+
+            blood_lost: 0,
+            bleeding: 0,
+            confused: 0,
+            suspicion: 0,
+            alerted: false,
+            grudges: HashMap::new(),
+        }
+    }
+
+    // create an undead version of this template, raised from
+    // the dead by a necromancer
+    pub fn raise_undead<R>(&self, rng: &mut R, master_id: u64, master: &Mob) -> Mob
+    where
+        R: Rng
+    {
+        let mut mob = self.generate_mob(rng);
+        mob.undead = true;
+        mob.max_age = None;
+        mob.master = Some(master_id);
+
+        // mobs that are opposed to life will turn on everything
+        // except the necromancer
+        if mob.opposed_to_life {
+            mob.alignment = MobAlignment::Hostile;
+        } else {
+            mob.alignment = master.alignment;
+            mob.faction = master.faction.clone();
         }
+
+        mob
+    }
+
+    // create a mob to fight for a summoner
+    pub fn summon<R>(&self, rng: &mut R, master_id: u64, master: &Mob) -> Mob
+    where
+        R: Rng
+    {
+        let mut mob = self.generate_mob(rng);
+        mob.master = Some(master_id);
+        mob.alignment = master.alignment;
+        mob.faction = master.faction.clone();
+        mob
+    }
+
+    // turn a mob of another template into one of this template,
+    // keeping everything it has seen, carried, and remembered, as
+    // well as whatever has happened to it (and whose side it's on)
+    pub fn mature<R>(&self, old: &Mob, rng: &mut R) -> Mob
+    where
+        R: Rng
+    {
+        let mut mob = self.generate_mob(rng);
+
+        // a summoned or raised mob stays loyal to its master, and
+        // keeps the alignment and faction its master gave it
+        mob.master = old.master;
+        if old.master.is_some() {
+            mob.alignment = old.alignment;
+            mob.faction = old.faction.clone();
+        }
+        if old.undead {
+            mob.undead = true;
+            mob.max_age = None;
+        }
+
+        // the adult's lifespan is rolled afresh, but it shouldn't
+        // have already run out (or the mob would die, or mature
+        // again, straight away)
+        mob.age = old.age;
+        mob.age_turns = old.age_turns;
+        mob.max_age = mob.max_age.map(|max| std::cmp::max(max, old.age + 1));
+
+        mob.current_mode = old.current_mode;
+        mob.fov = old.fov.clone();
+        mob.memory = old.memory.clone();
+        mob.sightings = old.sightings.clone();
+        mob.other_levels_memory = old.other_levels_memory.clone();
+        mob.inventory = old.inventory.clone();
+        mob.equipment = old.equipment.clone();
+        mob.hunger = old.hunger;
+        mob.thirst = old.thirst;
+        mob.tiredness = old.tiredness;
+        mob.blood_lost = old.blood_lost;
+        mob.bleeding = old.bleeding;
+        mob.confused = old.confused;
+        mob.suspicion = old.suspicion;
+        mob.alerted = old.alerted;
+        mob.grudges = old.grudges.clone();
+        mob.update_stats();
+        mob
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub enum MobMode {
     AttackMob,
     Eat,
     Drink,
     Sleep,
+    Unconscious,
     CompleteJob,
     FindJob,
     Wander,
 }
 
+// how sure a mob is that the player is around
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Awareness {
+    Unaware,
+    Suspicious,
+    Alerted,
+}
+
+impl Awareness {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Awareness::Unaware => "unaware",
+            Awareness::Suspicious => "suspicious",
+            Awareness::Alerted => "alerted",
+        }
+    }
+}
+
+// the last place a mob saw another mob
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MobSighting {
+    pub level: usize,
+    pub coords: Coord,
+
+    // how many ticks ago the sighting was made
+    // this is reset to zero whenever the mob is seen again
+    pub ticks_ago: usize,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Mob {
     // fields that are not unique to each specific mob (e.g. short_name,
@@ -343,6 +553,475 @@ pub struct Mob {
 
     pub fov: Vec<Coord>,
     pub memory: HashMap<Coord, DungeonTile>,
+
+    // where this mob last saw other mobs (including the player),
+    // indexed by mob id
+    #[serde(default)]
+    pub sightings: HashMap<u64, MobSighting>,
+
+    // memory of the levels this mob isn't on right now
+    #[serde(default)]
+    pub other_levels_memory: HashMap<usize, HashMap<Coord, DungeonTile>>,
+
+    #[serde(default)]
+    pub inventory: Vec<Item>,
+    #[serde(default)]
+    pub equipment: HashMap<EquipmentSlot, Item>,
+
+    // see NEED_MAX
+    #[serde(default)]
+    pub hunger: u16,
+    #[serde(default)]
+    pub thirst: u16,
+    #[serde(default)]
+    pub tiredness: u16,
+
+    // how many turns into the current year of its life the mob is
+    #[serde(default)]
+    pub age_turns: u64,
+
+    // the mob that summoned or raised this one
+    #[serde(default)]
+    pub master: Option<u64>,
+
+    // how many corpses this mob can keep raised at a time
+    #[serde(default)]
+    pub max_raised: u8,
+
+    // millilitres of blood lost, and how many millilitres
+    // are being lost every turn
+    #[serde(default)]
+    pub blood_lost: u32,
+    #[serde(default)]
+    pub bleeding: u32,
+
+    // how many more turns the mob will be confused for
+    #[serde(default)]
+    pub confused: u64,
+
+    // how much the mob suspects that the player is nearby
+    // see SUSPICIOUS_AT and ALERTED_AT
+    #[serde(default)]
+    pub suspicion: u16,
+
+    // whether the mob is sure where the player is (see CALM_AT)
+    #[serde(default)]
+    pub alerted: bool,
+
+    #[serde(default)]
+    pub faction: Option<String>,
+
+    // mobs that have attacked this one, and how many
+    // more turns the mob will hold it against them
+    #[serde(default)]
+    pub grudges: HashMap<u64, u64>,
+}
+
+impl Mob {
+    pub fn count_body_parts(&self, part: MobBody) -> usize {
+        self.body.iter().filter(|p| **p == part).count()
+    }
+
+    // each stat's name, maximum, and current percentage
+    // of that maximum
+    pub fn stats(&self) -> Vec<(&'static str, u8, u8)> {
+        vec![
+            ("strength", self.max_strength, self.strength),
+            ("agility", self.max_agility, self.agility),
+            ("endurance", self.max_endurance, self.endurance),
+            ("metabolism", self.max_metabolism, self.metabolism),
+            ("willpower", self.max_willpower, self.willpower),
+            ("focus", self.max_focus, self.focus),
+            ("intelligence", self.max_intelligence, self.intelligence),
+        ]
+    }
+
+    // the mob's actual strength, taking into account
+    // its current percentage of max_strength
+    pub fn current_strength(&self) -> usize {
+        (self.max_strength as usize * self.strength as usize) / 100
+    }
+
+    // the mob's actual agility, taking into account
+    // its current percentage of max_agility
+    pub fn current_agility(&self) -> usize {
+        (self.max_agility as usize * self.agility as usize) / 100
+    }
+
+    // put away the memory of the old level, and
+    // bring back the memory of the new one
+    pub fn switch_memory(&mut self, oldlevel: usize, newlevel: usize) {
+        let new_memory = self.other_levels_memory.remove(&newlevel)
+            .unwrap_or_default();
+        let old_memory = std::mem::replace(&mut self.memory, new_memory);
+        self.other_levels_memory.insert(oldlevel, old_memory);
+        self.fov = Vec::new();
+    }
+
+    pub fn equipment_slots(&self) -> Vec<EquipmentSlot> {
+        let mut slots = Vec::new();
+
+        for hand in 0..self.count_body_parts(MobBody::Hand) {
+            slots.push(EquipmentSlot::Hand(hand));
+        }
+
+        if self.count_body_parts(MobBody::Head) > 0 {
+            slots.push(EquipmentSlot::Head);
+        }
+
+        if self.count_body_parts(MobBody::Foot) > 0 {
+            slots.push(EquipmentSlot::Feet);
+        }
+
+        slots.push(EquipmentSlot::Armor);
+        slots.push(EquipmentSlot::Clothing);
+        slots
+    }
+
+    // weight of everything the mob is carrying and wearing,
+    // in grams
+    pub fn carried_weight(&self) -> usize {
+        self.inventory.iter()
+            .chain(self.equipment.values())
+            .map(|i| i.weight)
+            .sum()
+    }
+
+    pub fn max_carried_weight(&self) -> usize {
+        self.current_strength() * CARRY_WEIGHT_PER_STRENGTH
+    }
+
+    pub fn can_carry(&self, item: &Item) -> bool {
+        self.carried_weight() + item.weight <= self.max_carried_weight()
+    }
+
+    // wear or wield an item from the inventory. if every slot that
+    // the item could go into is taken, the item in the first slot
+    // is put back into the inventory.
+    pub fn equip(&mut self, index: usize) -> Result<EquipmentSlot, String> {
+        if index >= self.inventory.len() {
+            return Err("no such item".to_string());
+        }
+
+        let item_type = self.inventory[index].item_type;
+        let slots = self.equipment_slots().into_iter()
+            .filter(|s| s.accepts(item_type))
+            .collect::<Vec<EquipmentSlot>>();
+
+        if slots.is_empty() {
+            return Err(format!("can't wear or wield the {}",
+                self.inventory[index].name));
+        }
+
+        let slot = *slots.iter()
+            .find(|s| !self.equipment.contains_key(*s))
+            .unwrap_or(&slots[0]);
+
+        let item = self.inventory.remove(index);
+        if let Some(old) = self.equipment.insert(slot, item) {
+            self.inventory.push(old);
+        }
+
+        Ok(slot)
+    }
+
+    pub fn unequip(&mut self, slot: EquipmentSlot) -> Result<Item, String> {
+        match self.equipment.remove(&slot) {
+            Some(item) => {
+                self.inventory.push(item.clone());
+                Ok(item)
+            },
+            None => Err(format!("nothing on {}", slot.name())),
+        }
+    }
+
+    pub fn current_metabolism(&self) -> usize {
+        (self.max_metabolism as usize * self.metabolism as usize) / 100
+    }
+
+    pub fn current_focus(&self) -> usize {
+        (self.max_focus as usize * self.focus as usize) / 100
+    }
+
+    // change how suspicious the mob is of the player, becoming
+    // alerted at ALERTED_AT and calming down below CALM_AT
+    pub fn set_suspicion(&mut self, suspicion: u16) {
+        self.suspicion = std::cmp::min(suspicion, ALERTED_AT);
+        if self.suspicion >= ALERTED_AT {
+            self.alerted = true;
+        } else if self.suspicion < CALM_AT {
+            self.alerted = false;
+        }
+    }
+
+    pub fn awareness(&self) -> Awareness {
+        if self.alerted || self.suspicion >= ALERTED_AT {
+            Awareness::Alerted
+        } else if self.suspicion >= SUSPICIOUS_AT {
+            Awareness::Suspicious
+        } else {
+            Awareness::Unaware
+        }
+    }
+
+    // get hungrier, thirstier, and more tired (or more rested,
+    // if asleep) as time passes
+    pub fn tick_needs(&mut self, template: &MobTemplate, turns: u64) {
+        // why would a vampire eat or sleep
+        if template.vampire {
+            return;
+        }
+
+        let metabolism = self.current_metabolism() as u64;
+        let add = |meter: u16, amount: u64| {
+            std::cmp::min(meter as u64 + amount, NEED_MAX as u64) as u16
+        };
+
+        if template.needs_food {
+            self.hunger = add(self.hunger, metabolism * turns);
+        }
+
+        if template.needs_drink {
+            self.thirst = add(self.thirst, (metabolism * 3 / 2) * turns);
+        }
+
+        // undead don't feel exhaustion
+        if template.needs_sleep && !self.undead {
+            if self.current_mode == MobMode::Sleep {
+                let recovered = std::cmp::min(SLEEP_RECOVERY * turns,
+                    self.tiredness as u64) as u16;
+                self.tiredness -= recovered;
+                if self.tiredness == 0 {
+                    self.current_mode = MobMode::Wander;
+                }
+            } else {
+                self.tiredness = add(self.tiredness, TIREDNESS_RATE * turns);
+            }
+        }
+
+        self.update_stats();
+    }
+
+    // decide whether the mob should go and do something about its
+    // needs (or stop doing so, if they've been satisfied)
+    pub fn choose_mode(&mut self, template: &MobTemplate) {
+        match self.current_mode {
+            MobMode::Wander | MobMode::Eat | MobMode::Drink => (),
+            _ => return,
+        }
+
+        // vampires "eat" by drinking blood
+        if template.vampire {
+            self.current_mode = if self.blood_percentage() < VAMPIRE_HUNGRY_BLOOD {
+                MobMode::Eat
+            } else {
+                MobMode::Wander
+            };
+            return;
+        }
+
+        self.current_mode = if self.thirst >= NEED_URGENT {
+            MobMode::Drink
+        } else if self.hunger >= NEED_URGENT {
+            MobMode::Eat
+        } else if self.tiredness >= NEED_URGENT {
+            MobMode::Sleep
+        } else {
+            MobMode::Wander
+        };
+    }
+
+    pub fn eat(&mut self, food: &Item) {
+        let amount = std::cmp::min(food.weight as u64 * NEED_PER_GRAM,
+            self.hunger as u64) as u16;
+        self.hunger -= amount;
+        self.update_stats();
+    }
+
+    pub fn drink(&mut self, drink: &Item) {
+        let amount = std::cmp::min(drink.weight as u64 * NEED_PER_GRAM,
+            self.thirst as u64) as u16;
+        self.thirst -= amount;
+        self.update_stats();
+    }
+
+    // total blood supply, in millilitres
+    pub fn max_blood(&self) -> u32 {
+        self.weight as u32 * (BLOOD_PER_KG + self.max_endurance as u32)
+    }
+
+    pub fn blood(&self) -> u32 {
+        self.max_blood().saturating_sub(self.blood_lost)
+    }
+
+    pub fn blood_percentage(&self) -> u32 {
+        if self.max_blood() == 0 {
+            return 100;
+        }
+
+        (self.blood() * 100) / self.max_blood()
+    }
+
+    // open a wound that bleeds some millilitres every turn
+    pub fn wound(&mut self, bleeding: u32) {
+        self.bleeding += bleeding;
+    }
+
+    // returns how much blood was actually lost
+    pub fn lose_blood(&mut self, amount: u32) -> u32 {
+        let lost = std::cmp::min(amount, self.blood());
+        self.blood_lost += lost;
+        self.update_stats();
+        lost
+    }
+
+    pub fn gain_blood(&mut self, amount: u32) {
+        self.blood_lost = self.blood_lost.saturating_sub(amount);
+        self.update_stats();
+    }
+
+    // bleed, clot, and regenerate lost blood (or, for vampires, use it
+    // up) as time passes. returns true if the mob has no blood left.
+    pub fn tick_blood(&mut self, template: &MobTemplate, turns: u64) -> bool {
+        // the undead (except vampires) don't have much
+        // use for blood
+        if self.undead && !template.vampire {
+            return false;
+        }
+
+        // wounds bleed a millilitre less every turn as they clot
+        let clotting = std::cmp::min(turns, self.bleeding as u64);
+        let bled = clotting * self.bleeding as u64
+            - (clotting * clotting.saturating_sub(1)) / 2;
+        self.bleeding -= clotting as u32;
+
+        let mut lost = self.blood_lost as u64 + bled;
+        if template.vampire {
+            lost += VAMPIRE_BLOOD_USE * turns;
+        } else {
+            let regen = self.current_metabolism() as u64 * BLOOD_REGEN * turns;
+            lost = lost.saturating_sub(regen);
+        }
+        self.blood_lost = std::cmp::min(lost, self.max_blood() as u64) as u32;
+
+        if self.blood() == 0 {
+            return true;
+        }
+
+        let percentage = self.blood_percentage();
+        if percentage < FAINT_BLOOD {
+            self.current_mode = MobMode::Unconscious;
+        } else if percentage >= RECOVER_BLOOD && self.current_mode == MobMode::Unconscious {
+            self.current_mode = MobMode::Wander;
+            self.confused = FAINT_CONFUSION;
+        }
+
+        self.update_stats();
+        false
+    }
+
+    // remember that another mob attacked this one
+    pub fn hold_grudge(&mut self, against: u64) {
+        self.grudges.insert(against, GRUDGE_TURNS);
+    }
+
+    pub fn tick_grudges(&mut self, turns: u64) {
+        for left in self.grudges.values_mut() {
+            *left = left.saturating_sub(turns);
+        }
+        self.grudges.retain(|_, left| *left > 0);
+    }
+
+    // should this mob attack the other one, given the chance?
+    pub fn hostile_to(&self, self_id: u64, other: &Mob, other_id: u64) -> bool {
+        if self_id == other_id {
+            return false;
+        }
+
+        // whatever else is going on, nobody gets away
+        // with attacking this mob
+        if self.grudges.contains_key(&other_id) {
+            return true;
+        }
+
+        // undead that are opposed to life will attack anything
+        // except their master, faction or no faction, even the
+        // other minions (living or undead) of their master
+        if self.opposed_to_life {
+            return self.master != Some(other_id);
+        }
+
+        // otherwise it won't turn on its master, its minions,
+        // or its master's other minions
+        if self.master == Some(other_id) || other.master == Some(self_id)
+            || (self.master.is_some() && self.master == other.master) {
+                return false;
+        }
+
+        if self.faction.is_some() && self.faction == other.faction {
+            return false;
+        }
+
+        match (self.alignment, other.alignment) {
+            // hostile mobs only fight each other when they
+            // belong to rival factions
+            (MobAlignment::Hostile, MobAlignment::Hostile) =>
+                self.faction.is_some() && other.faction.is_some(),
+            (MobAlignment::Hostile, _) => true,
+            (MobAlignment::Friendly, MobAlignment::Hostile) => true,
+            _ => false,
+        }
+    }
+
+    pub fn tick_confusion(&mut self, turns: u64) {
+        self.confused = self.confused.saturating_sub(turns);
+    }
+
+    // work out the mob's current percentages of its max stats
+    // from how hungry, thirsty, tired and bloodless it is
+    pub fn update_stats(&mut self) {
+        let hunger = need_penalty(self.hunger);
+        let thirst = need_penalty(self.thirst);
+        let tiredness = need_penalty(self.tiredness);
+        let blood = blood_penalty(self.blood_percentage());
+
+        let pct = |penalty: u16| {
+            std::cmp::max(100u16.saturating_sub(penalty),
+                MIN_STAT_PERCENTAGE) as u8
+        };
+
+        self.strength = pct(hunger + thirst / 2 + blood);
+        self.endurance = pct(hunger / 2 + thirst + blood);
+        self.agility = pct(thirst / 2 + tiredness / 2 + blood);
+        self.willpower = pct(hunger / 2 + tiredness / 2);
+        self.focus = pct(tiredness + hunger / 2);
+        self.intelligence = pct(tiredness / 2);
+    }
+
+    // grow older. returns true if the mob has reached its max_age,
+    // and should die (or mature)
+    pub fn tick_age(&mut self, turns: u64) -> bool {
+        self.age_turns += turns;
+        self.age += self.age_turns / TURNS_PER_YEAR;
+        self.age_turns %= TURNS_PER_YEAR;
+
+        // undead creatures do not die of old age
+        if self.undead {
+            return false;
+        }
+
+        match self.max_age {
+            Some(max_age) => self.age >= max_age,
+            None => false,
+        }
+    }
+
+    pub fn is_awake(&self) -> bool {
+        match self.current_mode {
+            MobMode::Sleep | MobMode::Unconscious => false,
+            _ => true,
+        }
+    }
 }
 
 impl std::hash::Hash for Mob {
@@ -370,3 +1049,108 @@ impl std::hash::Hash for Mob {
         self.max_intelligence.hash(state);
     }
 }
+
+// how much a need lowers a mob's stats, as a percentage
+fn need_penalty(meter: u16) -> u16 {
+    if meter <= NEED_URGENT {
+        0
+    } else {
+        (((meter - NEED_URGENT) as u32 * 80) / (NEED_MAX - NEED_URGENT) as u32) as u16
+    }
+}
+
+// how much losing blood lowers a mob's stats, as a percentage
+fn blood_penalty(percentage: u32) -> u16 {
+    if percentage >= 75 {
+        0
+    } else {
+        (((75 - percentage) * 60) / 75) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info_files::*;
+
+    fn templates() -> HashMap<String, MobTemplate> {
+        load_info_files("../dat/mobs/").unwrap()
+    }
+
+    #[test]
+    fn matured_mob_keeps_its_master_and_status() {
+        let templates = templates();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let master = templates["elf"].generate_mob(&mut rng);
+        let mut old = templates["internet_troll"].summon(&mut rng, 1, &master);
+        old.confused = 12;
+        old.hold_grudge(2);
+
+        let new = templates["elf"].mature(&old, &mut rng);
+        assert_eq!(new.from_mob_template, "elf");
+        assert_eq!(new.master, Some(1));
+        assert_eq!(new.alignment, master.alignment);
+        assert_eq!(new.faction, master.faction);
+        assert_eq!(new.confused, 12);
+        assert!(new.grudges.contains_key(&2));
+    }
+
+    #[test]
+    fn matured_mob_without_master_takes_the_new_side() {
+        let templates = templates();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let old = templates["internet_troll"].generate_mob(&mut rng);
+        let new = templates["elf"].mature(&old, &mut rng);
+        assert_eq!(new.master, None);
+        assert_eq!(new.faction, templates["elf"].faction);
+    }
+
+    #[test]
+    fn matured_mob_keeps_its_age_and_lives_on() {
+        let templates = templates();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // older than any troll is meant to get
+        let mut old = templates["elf"].generate_mob(&mut rng);
+        old.age = 100;
+        old.age_turns = TURNS_PER_YEAR / 2;
+
+        let mut new = templates["internet_troll"].mature(&old, &mut rng);
+        assert_eq!(new.age, 100);
+        assert_eq!(new.age_turns, TURNS_PER_YEAR / 2);
+        assert!(new.max_age.unwrap() > 100);
+        assert!(!new.tick_age(1));
+    }
+
+    #[test]
+    fn opposed_to_life_undead_only_spare_their_master() {
+        let templates = templates();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let necromancer = templates["elf"].generate_mob(&mut rng);
+        let mut undead = templates["elf"].raise_undead(&mut rng, 1, &necromancer);
+        undead.opposed_to_life = true;
+        let summoned = templates["elf"].summon(&mut rng, 1, &necromancer);
+
+        assert!(!undead.hostile_to(2, &necromancer, 1));
+        assert!(undead.hostile_to(2, &summoned, 3));
+        assert!(!summoned.hostile_to(3, &necromancer, 1));
+    }
+
+    #[test]
+    fn opposed_to_life_undead_attack_other_undead() {
+        let templates = templates();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let necromancer = templates["elf"].generate_mob(&mut rng);
+        let mut undead = templates["elf"].raise_undead(&mut rng, 1, &necromancer);
+        undead.opposed_to_life = true;
+        let other = templates["internet_troll"].raise_undead(&mut rng, 1, &necromancer);
+        let stranger = templates["internet_troll"].raise_undead(&mut rng, 5, &other);
+
+        assert!(undead.hostile_to(2, &other, 3));
+        assert!(undead.hostile_to(2, &stranger, 4));
+    }
+}
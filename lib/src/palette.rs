@@ -0,0 +1,197 @@
+// squeezing colours into what the terminal can show
+//
+// everything is drawn in 24-bit colour, but plenty of terminals (and
+// tmux, and serial consoles) can only show 256, 16, or 8 colours. the
+// smaller palettes here are xterm's, and colours are matched to the
+// nearest one in them.
+
+// the 16 ANSI colours, as xterm shows them. the first
+// 8 are all that a basic terminal has.
+const ANSI_COLORS: [u32; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00,
+    0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+    0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00,
+    0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+];
+
+// the levels of each channel in the 256-colour palette's 6x6x6 cube
+const CUBE_LEVELS: [u32; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Palette {
+    TrueColor,
+    Colors256,
+    Colors16,
+    Colors8,
+}
+
+impl Palette {
+    // "true" (or "24bit"), "256", "16", or "8"
+    pub fn from_name(name: &str) -> Option<Palette> {
+        match name {
+            "true" | "truecolor" | "24bit" => Some(Palette::TrueColor),
+            "256" => Some(Palette::Colors256),
+            "16" => Some(Palette::Colors16),
+            "8" => Some(Palette::Colors8),
+            _ => None,
+        }
+    }
+
+    // guess what the terminal can do from $COLORTERM and $TERM
+    pub fn detect() -> Palette {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Palette::TrueColor
+        } else if term.contains("256color") {
+            Palette::Colors256
+        } else if term == "linux" || term == "vt100" || term == "vt220"
+            || term.is_empty() || term == "dumb" {
+                Palette::Colors8
+        } else {
+            Palette::Colors16
+        }
+    }
+
+    // the nearest colour in the palette to a 0xRRGGBB colour, as an
+    // index into the palette (or just the colour, for truecolor)
+    pub fn index(&self, rgb: u32) -> u32 {
+        match self {
+            Palette::TrueColor => rgb,
+            Palette::Colors256 => nearest_256(rgb),
+            Palette::Colors16 => nearest(rgb, &ANSI_COLORS),
+            Palette::Colors8 => nearest(rgb, &ANSI_COLORS[..8]),
+        }
+    }
+
+    // the colour a palette index stands for
+    pub fn color(&self, index: u32) -> u32 {
+        match self {
+            Palette::TrueColor => index,
+            Palette::Colors256 => color_256(index),
+            Palette::Colors16 | Palette::Colors8 => ANSI_COLORS[index as usize],
+        }
+    }
+
+    // a colour, as close as the palette gets to it
+    pub fn reduce(&self, rgb: u32) -> u32 {
+        self.color(self.index(rgb))
+    }
+}
+
+fn channels(rgb: u32) -> (i64, i64, i64) {
+    (((rgb >> 16) & 0xff) as i64, ((rgb >> 8) & 0xff) as i64, (rgb & 0xff) as i64)
+}
+
+// how different two colours look. plain distance in RGB overrates
+// differences in blue and underrates those in green, so the channels
+// are weighted (the "redmean" approximation).
+fn distance(a: u32, b: u32) -> i64 {
+    let (r1, g1, b1) = channels(a);
+    let (r2, g2, b2) = channels(b);
+    let rmean = (r1 + r2) / 2;
+    let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+
+    (((512 + rmean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - rmean) * db * db) >> 8)
+}
+
+fn nearest(rgb: u32, colors: &[u32]) -> u32 {
+    (0..colors.len())
+        .min_by_key(|i| distance(rgb, colors[*i]))
+        .unwrap() as u32
+}
+
+fn nearest_level(channel: i64) -> usize {
+    (0..CUBE_LEVELS.len())
+        .min_by_key(|i| (CUBE_LEVELS[*i] as i64 - channel).abs())
+        .unwrap()
+}
+
+// the 16 system colours are left out, since terminals
+// disagree about what they look like; the closer of the
+// nearest colour in the cube and the nearest grey wins
+fn nearest_256(rgb: u32) -> u32 {
+    let (r, g, b) = channels(rgb);
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+
+    let average = (r + g + b) / 3;
+    let grey = 232 + std::cmp::min(std::cmp::max(average - 3, 0) / 10, 23) as usize;
+
+    if distance(rgb, color_256(grey as u32)) < distance(rgb, color_256(cube as u32)) {
+        grey as u32
+    } else {
+        cube as u32
+    }
+}
+
+fn color_256(index: u32) -> u32 {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            let (r, g, b) = (i / 36, (i / 6) % 6, i % 6);
+            (CUBE_LEVELS[r as usize] << 16) | (CUBE_LEVELS[g as usize] << 8)
+                | CUBE_LEVELS[b as usize]
+        },
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level << 16) | (level << 8) | level
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_weights_the_channels() {
+        assert_eq!(distance(0x123456, 0x123456), 0);
+        assert_eq!(distance(0x000000, 0xff0000), 162308);
+        assert_eq!(distance(0x000000, 0x00ff00), 260100);
+        assert_eq!(distance(0x000000, 0x0000ff), 194820);
+        assert_eq!(distance(0xff0000, 0x00ff00), distance(0x00ff00, 0xff0000));
+    }
+
+    #[test]
+    fn nearest_16_and_8() {
+        assert_eq!(Palette::Colors16.index(0xff0000), 9);
+        assert_eq!(Palette::Colors8.index(0xff0000), 1);
+        assert_eq!(Palette::Colors16.index(0xffffff), 15);
+        assert_eq!(Palette::Colors8.index(0xffffff), 7);
+        assert_eq!(Palette::Colors8.reduce(0xffffff), 0xe5e5e5);
+    }
+
+    #[test]
+    fn nearest_256_cube() {
+        assert_eq!(nearest_256(0x000000), 16);
+        assert_eq!(nearest_256(0xff0000), 196);
+        assert_eq!(nearest_256(0x5fd7ff), 81);
+        assert_eq!(nearest_256(0x5ad0fa), 81);
+    }
+
+    #[test]
+    fn nearest_256_greys() {
+        for grey in &[0x080808, 0x444444, 0x808080, 0xbcbcbc, 0xeeeeee] {
+            let index = nearest_256(*grey);
+            assert!(index >= 232 && index <= 255, "{:06x} -> {}", grey, index);
+        }
+        assert_eq!(nearest_256(0x808080), 244);
+    }
+
+    #[test]
+    fn every_256_color_is_its_own_nearest() {
+        for index in 16..256 {
+            assert_eq!(nearest_256(color_256(index)), index);
+        }
+    }
+
+    #[test]
+    fn palettes_by_name() {
+        assert_eq!(Palette::from_name("24bit"), Some(Palette::TrueColor));
+        assert_eq!(Palette::from_name("256"), Some(Palette::Colors256));
+        assert_eq!(Palette::from_name("8"), Some(Palette::Colors8));
+        assert_eq!(Palette::from_name("lots"), None);
+    }
+}
@@ -0,0 +1,54 @@
+// pathfinding on a level's grid of tiles
+//
+// every step (diagonal or not) costs the same, so a
+// breadth-first search finds the shortest path.
+
+use crate::coord::*;
+use crate::dirs::*;
+use std::collections::{HashMap, VecDeque};
+
+// find the shortest path from start to the nearest tile that
+// is_goal accepts, only walking through tiles that passable
+// accepts. the path doesn't include start, and is empty if
+// start is itself a goal.
+pub fn find_path<P, G>(width: usize, height: usize, start: (usize, usize),
+    passable: P, is_goal: G) -> Option<Vec<(usize, usize)>>
+where
+    P: Fn((usize, usize)) -> bool,
+    G: Fn((usize, usize)) -> bool,
+{
+    // which tile each visited tile was reached from
+    let mut came_from: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    came_from.insert(start, start);
+
+    while let Some(cur) = queue.pop_front() {
+        if is_goal(cur) {
+            let mut path = Vec::new();
+            let mut step = cur;
+            while step != start {
+                path.push(step);
+                step = came_from[&step];
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for d in Direction::all().iter() {
+            let next: (usize, usize) = Coord::from(cur).neighbor_in_direction(*d)
+                .clamp_x(width - 1)
+                .clamp_y(height - 1)
+                .as_yx();
+
+            if came_from.contains_key(&next) || !passable(next) {
+                continue;
+            }
+
+            came_from.insert(next, cur);
+            queue.push_back(next);
+        }
+    }
+
+    None
+}
@@ -6,10 +6,58 @@ use rand::prelude::*;
 use crate::utils;
 use std::collections::HashMap;
 
+// how many points each thing the player does (or has done to them)
+// adds or takes away from their stealth score
+const STEALTH_PER_LEVEL: u64 = 10;
+const STEALTH_PER_UNSEEN_KILL: u64 = 15;
+const STEALTH_PER_SPOTTING: u64 = 3;
+
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {
     pub coords: (usize, usize),
     pub level: usize,
+
+    #[serde(default)]
+    pub stealth: StealthRecord,
+}
+
+// a tally of how sneaky the player has been so far
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StealthRecord {
+    // how many times a mob has noticed the player
+    pub times_spotted: u64,
+
+    // how many mobs the player has killed, and how
+    // many of those never saw it coming
+    #[serde(default)]
+    pub kills: u64,
+    pub unseen_kills: u64,
+
+    // the deepest level the player has reached
+    pub deepest_level: usize,
+}
+
+impl StealthRecord {
+    pub fn spotted(&mut self) {
+        self.times_spotted += 1;
+    }
+
+    pub fn killed(&mut self, seen: bool) {
+        self.kills += 1;
+        if !seen {
+            self.unseen_kills += 1;
+        }
+    }
+
+    pub fn reached_level(&mut self, level: usize) {
+        self.deepest_level = std::cmp::max(self.deepest_level, level);
+    }
+
+    pub fn score(&self) -> u64 {
+        let gained = self.deepest_level as u64 * STEALTH_PER_LEVEL
+            + self.unseen_kills * STEALTH_PER_UNSEEN_KILL;
+        gained.saturating_sub(self.times_spotted * STEALTH_PER_SPOTTING)
+    }
 }
 
 impl Player {
@@ -58,6 +106,7 @@ impl Player {
                 return Player {
                     coords: *coord,
                     level: level_no,
+                    stealth: StealthRecord::default(),
                 };
             }
         }
@@ -72,6 +121,7 @@ impl Player {
         Player {
             coords: coord,
             level: level_no,
+            stealth: StealthRecord::default(),
         }
     }
 }
@@ -1,3 +1,6 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Priority {
     Min,
     VLow,
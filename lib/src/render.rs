@@ -0,0 +1,342 @@
+// where the screen gets drawn to
+//
+// th and mv draw everything through a Renderer, rather than calling
+// termbox themselves. they share a termbox renderer for actually
+// playing (see termbox.rs); the CellBuffer here just keeps the screen
+// in memory, so that it can be looked at afterwards (e.g. by a test)
+// or dumped out as text.
+//
+// colours are 0xRRGGBB, as termbox's truecolor mode wants them; it's
+// up to the renderer to make do with fewer (see palette.rs).
+
+use crate::items::*;
+use crate::material::*;
+use crate::mob::*;
+use crate::palette::*;
+use std::fmt::Write;
+
+// what the screen is cleared to
+pub const CLEAR_FG: u32 = 0xffffff;
+pub const CLEAR_BG: u32 = 0x000000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: u32,
+    pub bg: u32,
+}
+
+impl Cell {
+    pub fn new(ch: char, fg: u32, bg: u32) -> Cell {
+        Cell { ch: ch, fg: fg, bg: bg }
+    }
+
+    pub fn blank() -> Cell {
+        Cell::new(' ', CLEAR_FG, CLEAR_BG)
+    }
+}
+
+// which characters things are drawn with. some terminals
+// (and fonts) can only manage ASCII.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Glyphs {
+    Unicode,
+    Ascii,
+}
+
+impl Glyphs {
+    pub fn mob(&self, mob: &Mob) -> char {
+        match self {
+            Glyphs::Unicode => mob.unicode_glyph,
+            Glyphs::Ascii => mob.ascii_glyph,
+        }
+    }
+
+    pub fn block(&self, material: &MaterialInfo) -> char {
+        match self {
+            Glyphs::Unicode => material.block_glyph,
+            Glyphs::Ascii => material.ascii_block_glyph,
+        }
+    }
+
+    // items only have the one glyph
+    pub fn item(&self, item: &Item) -> char {
+        match self {
+            Glyphs::Ascii if !item.glyph.is_ascii() => '&',
+            _ => item.glyph,
+        }
+    }
+
+    pub fn floor(&self) -> char {
+        match self {
+            Glyphs::Unicode => '·',
+            Glyphs::Ascii => '.',
+        }
+    }
+}
+
+// how to draw things, from the command line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayOptions {
+    pub glyphs: Glyphs,
+    pub palette: Palette,
+}
+
+impl DisplayOptions {
+    // take --ascii and --colors <true|256|16|8> out of the arguments,
+    // guessing the palette from the environment if it isn't given
+    pub fn from_args(args: &mut Vec<String>) -> Result<DisplayOptions, String> {
+        let mut options = DisplayOptions {
+            glyphs: Glyphs::Unicode,
+            palette: Palette::detect(),
+        };
+
+        if let Some(i) = args.iter().position(|a| a == "--ascii") {
+            args.remove(i);
+            options.glyphs = Glyphs::Ascii;
+        }
+
+        if let Some(i) = args.iter().position(|a| a == "--colors") {
+            args.remove(i);
+            if i >= args.len() {
+                return Err("--colors needs a palette (true, 256, 16, or 8)".to_string());
+            }
+
+            let name = args.remove(i);
+            options.palette = Palette::from_name(&name)
+                .ok_or(format!("unknown palette \"{}\"", name))?;
+        }
+
+        Ok(options)
+    }
+}
+
+pub trait Renderer {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    fn clear(&mut self);
+
+    // cells outside the screen are ignored
+    fn put_cell(&mut self, x: i32, y: i32, cell: Cell);
+
+    // show whatever's been drawn since the last time
+    fn present(&mut self) {}
+
+    // give the terminal (or whatever) back
+    fn close(&mut self) {}
+
+    // draw a string, wrapping it (or cutting it short with an ellipsis)
+    // at max_x, and clearing the rest of the line. returns the row after
+    // the last one drawn on, and the column the string stopped at.
+    fn put_string(
+        &mut self,
+        max_x: i32, max_y: i32,
+        col: i32, row: i32,
+        str: &str,
+        fg: u32, bg: u32,
+        wrap: bool
+    ) -> (i32, i32) {
+        let mut ccol = col;
+        let mut crow = row;
+        let mut cut_short = false;
+        for c in str.chars() {
+            self.put_cell(ccol, crow, Cell::new(c, fg, bg));
+
+            if (ccol + 1) == (max_x - 1) {
+                if wrap && crow + 1 != max_y {
+                    crow += 1;
+                    ccol = col;
+                } else {
+                    // draw some nice ellipses
+                    let dot = Cell::new('.', fg, bg);
+                    self.put_cell(ccol - 2, crow, dot);
+                    self.put_cell(ccol - 1, crow, dot);
+                    self.put_cell(ccol - 0, crow, dot);
+
+                    cut_short = true;
+                    break;
+                }
+            } else {
+                ccol += 1;
+            }
+        }
+
+        // clear to the end of the (last) line, without
+        // rubbing out the ellipsis
+        let clear_from = if cut_short { ccol + 1 } else { ccol };
+        for ncol in clear_from..max_x {
+            self.put_cell(ncol, crow, Cell::blank());
+        }
+
+        (crow + 1, ccol)
+    }
+}
+
+// a screen that only exists in memory
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellBuffer {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+}
+
+impl CellBuffer {
+    pub fn new(width: i32, height: i32) -> CellBuffer {
+        CellBuffer {
+            width: width,
+            height: height,
+            cells: vec![Cell::blank(); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.cells[(y * self.width + x) as usize])
+    }
+
+    pub fn resize(&mut self, width: i32, height: i32) {
+        *self = CellBuffer::new(width, height);
+    }
+
+    // one row of the screen, as text
+    pub fn row(&self, y: i32) -> String {
+        (0..self.width)
+            .filter_map(|x| self.get(x, y))
+            .map(|c| c.ch)
+            .collect()
+    }
+
+    // the screen as plain text, without any colours or
+    // the spaces at the end of each line
+    pub fn as_plain(&self) -> String {
+        let mut out = String::new();
+        for y in 0..self.height {
+            out.push_str(self.row(y).trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    // the screen as text with ANSI colour codes, to be
+    // dumped out to a terminal that can show the palette
+    pub fn as_ansi(&self, palette: Palette) -> String {
+        let mut out = String::new();
+        for y in 0..self.height {
+            let mut last: Option<(u32, u32)> = None;
+            for x in 0..self.width {
+                let cell = self.get(x, y).unwrap();
+                let colors = (palette.index(cell.fg), palette.index(cell.bg));
+                if last != Some(colors) {
+                    let _ = write!(out, "\x1b[{};{}m",
+                        ansi_color(palette, colors.0, false),
+                        ansi_color(palette, colors.1, true));
+                    last = Some(colors);
+                }
+                out.push(cell.ch);
+            }
+            out.push_str("\x1b[0m\n");
+        }
+        out
+    }
+}
+
+impl Renderer for CellBuffer {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn clear(&mut self) {
+        for cell in &mut self.cells {
+            *cell = Cell::blank();
+        }
+    }
+
+    fn put_cell(&mut self, x: i32, y: i32, cell: Cell) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return;
+        }
+        self.cells[(y * self.width + x) as usize] = cell;
+    }
+}
+
+// the SGR parameters to set the foreground (or background)
+// to one of a palette's colours
+fn ansi_color(palette: Palette, index: u32, bg: bool) -> String {
+    let base = if bg { 40 } else { 30 };
+    match palette {
+        Palette::TrueColor => format!("{};2;{};{};{}", base + 8,
+            (index >> 16) & 0xff, (index >> 8) & 0xff, index & 0xff),
+        Palette::Colors256 => format!("{};5;{}", base + 8, index),
+        Palette::Colors16 | Palette::Colors8 => match index {
+            0..=7 => format!("{}", base + index),
+            _ => format!("{}", base + 60 + index - 8),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a tiny screen: a line of red text, and a white @
+    // on a blue background underneath it
+    fn screen() -> CellBuffer {
+        let mut buf = CellBuffer::new(6, 2);
+        buf.put_string(6, 2, 0, 0, "hi", 0xff0000, 0x000000, false);
+        buf.put_cell(2, 1, Cell::new('@', 0xffffff, 0x0000ff));
+        buf
+    }
+
+    #[test]
+    fn plain_snapshot() {
+        assert_eq!(screen().as_plain(), "hi\n  @\n");
+    }
+
+    #[test]
+    fn ansi_snapshot_16_colors() {
+        assert_eq!(screen().as_ansi(Palette::Colors16), concat!(
+            "\x1b[91;40mhi\x1b[97;40m    \x1b[0m\n",
+            "\x1b[97;40m  \x1b[97;44m@\x1b[97;40m   \x1b[0m\n"));
+    }
+
+    #[test]
+    fn ansi_snapshot_truecolor() {
+        assert_eq!(screen().as_ansi(Palette::TrueColor), concat!(
+            "\x1b[38;2;255;0;0;48;2;0;0;0mhi",
+            "\x1b[38;2;255;255;255;48;2;0;0;0m    \x1b[0m\n",
+            "\x1b[38;2;255;255;255;48;2;0;0;0m  ",
+            "\x1b[38;2;255;255;255;48;2;0;0;255m@",
+            "\x1b[38;2;255;255;255;48;2;0;0;0m   \x1b[0m\n"));
+    }
+
+    #[test]
+    fn long_strings_are_cut_short() {
+        let mut buf = CellBuffer::new(10, 1);
+        let end = buf.put_string(10, 1, 0, 0, "hello, world", 0xffffff, 0, false);
+        assert_eq!(end, (1, 8));
+        assert_eq!(buf.as_plain(), "hello,...\n");
+    }
+
+    #[test]
+    fn long_strings_wrap() {
+        let mut buf = CellBuffer::new(6, 3);
+        buf.put_string(6, 3, 0, 2, "xxxxxx", 0xffffff, 0, false);
+        let end = buf.put_string(6, 3, 1, 0, "abcdefg", 0xffffff, 0, true);
+        assert_eq!(end, (2, 4));
+        assert_eq!(buf.as_plain(), " abcd\n efg\nxx...\n");
+    }
+
+    #[test]
+    fn clearing_blanks_the_screen() {
+        let mut buf = screen();
+        buf.clear();
+        assert_eq!(buf, CellBuffer::new(6, 2));
+        assert_eq!(buf.as_plain(), "\n\n");
+    }
+}
@@ -0,0 +1,232 @@
+// saved games
+//
+// a save is kept apart from the map the game was started from, so
+// that playing never touches the generated world. it begins with a
+// header saying which version of the format it's in; older saves are
+// migrated when they're loaded, and newer ones are refused.
+//
+// saves (and maps, which are just saves nobody's played yet) can be in
+// any of the encodings in encoding.rs.
+
+use chrono::prelude::*;
+use crate::dungeon::*;
+use crate::encoding::*;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::io::{self, Write};
+
+// bump this whenever a change to the dungeon can't be dealt with
+// by a #[serde(default)] alone, and add a step to migrate()
+//
+// version 0 was the old way of saving, where the dungeon was
+// written straight back over the map
+//
+// note that the binary encodings don't know about field names, so
+// #[serde(default)] doesn't help them: any change at all to what's
+// saved needs a new version for binary saves to load.
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveHeader {
+    pub format_version: u32,
+
+    // the version of the game that wrote the save
+    pub game_version: String,
+
+    // the seed the world was generated from
+    pub seed: u64,
+
+    // when the save was written (a unix timestamp)
+    pub saved_on: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub header: SaveHeader,
+    pub dungeon: Dungeon,
+}
+
+// used to read just the header, without caring
+// whether the rest of the save makes sense
+#[derive(Deserialize)]
+struct HeaderOnly {
+    header: SaveHeader,
+}
+
+// the same as SaveFile, but borrowing the dungeon so
+// it doesn't have to be cloned to be saved
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    header: SaveHeader,
+    dungeon: &'a Dungeon,
+}
+
+impl SaveHeader {
+    pub fn new(dungeon: &Dungeon, game_version: &str) -> SaveHeader {
+        SaveHeader {
+            format_version: SAVE_FORMAT_VERSION,
+            game_version: game_version.to_string(),
+            seed: dungeon.seed,
+            saved_on: Local::now().timestamp(),
+        }
+    }
+}
+
+impl SaveFile {
+    pub fn new(dungeon: Dungeon, game_version: &str) -> SaveFile {
+        SaveFile {
+            header: SaveHeader::new(&dungeon, game_version),
+            dungeon: dungeon,
+        }
+    }
+
+    pub fn dungeon_to_bytes(dungeon: &Dungeon, game_version: &str,
+        encoding: Encoding) -> Result<Vec<u8>, String>
+    {
+        let save = SaveFileRef {
+            header: SaveHeader::new(dungeon, game_version),
+            dungeon: dungeon,
+        };
+        encode(&save, encoding)
+    }
+
+    pub fn to_bytes(&self, encoding: Encoding) -> Result<Vec<u8>, String> {
+        encode(self, encoding)
+    }
+
+    // load a save, migrating it from an older format if need be
+    pub fn from_bytes(data: &[u8]) -> Result<SaveFile, String> {
+        let header = match decode::<HeaderOnly>(data) {
+            Ok(h) => h.header,
+
+            // no header at all, so this is either an old save
+            // or an old map (only RON ones are that old)
+            Err(_) if Encoding::detect(data) == Encoding::Ron => {
+                let mut dungeon: Dungeon = decode(data)
+                    .map_err(|e| format!("not a save or a map: {}", e))?;
+                dungeon.index_mobs();
+                return migrate(SaveFile::new(dungeon, "unknown"), 0);
+            },
+            Err(e) => return Err(format!("corrupt save: {}", e)),
+        };
+
+        if header.format_version > SAVE_FORMAT_VERSION {
+            return Err(format!("save is too new (format version {}, written by \
+                version {}); this version only understands up to format version {}",
+                header.format_version, header.game_version, SAVE_FORMAT_VERSION));
+        }
+
+        let mut save: SaveFile = decode(data)
+            .map_err(|e| format!("corrupt save: {}", e))?;
+        save.dungeon.index_mobs();
+        migrate(save, header.format_version)
+    }
+}
+
+// bring a save in some older format up to date, one
+// version at a time
+fn migrate(mut save: SaveFile, from: u32) -> Result<SaveFile, String> {
+    for version in from..SAVE_FORMAT_VERSION {
+        match version {
+            // old saves were plain maps, and need nothing
+            // more than the header they've already been given
+            0 => (),
+            // a version was bumped without a step being added here
+            _ => return Err(format!("don't know how to migrate a save \
+                from format version {}", version)),
+        }
+    }
+
+    save.header.format_version = SAVE_FORMAT_VERSION;
+    Ok(save)
+}
+
+// write a file so that it's either entirely replaced or left
+// alone, even if the game dies halfway through: the data goes to
+// a temporary file next to it first, which is then renamed over
+// the real one
+pub fn write_atomically(path: &str, data: &[u8]) -> io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dun_s1::*;
+    use crate::dun_s2::*;
+    use crate::info_files::*;
+    use crate::mob::*;
+    use rand::prelude::*;
+    use std::collections::HashMap;
+
+    fn dungeon() -> Dungeon {
+        let templates: HashMap<String, MobTemplate> =
+            load_info_files("../dat/mobs/").unwrap();
+        let mut s1 = DungeonS1::new(5, 5);
+        s1.set(2, 2, TileType::Floor);
+        let mut rng = StdRng::seed_from_u64(42);
+        Dungeon::from_dungeon_s2("test".to_string(),
+            &mut vec![DungeonS2::from_dungeon_s1(&s1)], &mut rng,
+            &templates["elf"], HashMap::new())
+    }
+
+    #[test]
+    fn header_is_read_back_in_every_encoding() {
+        let dungeon = dungeon();
+        for &encoding in &[Encoding::Ron, Encoding::Binary, Encoding::Compressed] {
+            let data = SaveFile::dungeon_to_bytes(&dungeon, "1.2.3", encoding).unwrap();
+            let save = SaveFile::from_bytes(&data).unwrap();
+
+            assert_eq!(save.header.format_version, SAVE_FORMAT_VERSION);
+            assert_eq!(save.header.game_version, "1.2.3");
+            assert_eq!(save.header.seed, dungeon.seed);
+            assert_eq!(save.dungeon.checksum(), dungeon.checksum());
+        }
+    }
+
+    #[test]
+    fn old_maps_without_a_header_are_migrated() {
+        let dungeon = dungeon();
+        let data = encode(&dungeon, Encoding::Ron).unwrap();
+        let save = SaveFile::from_bytes(&data).unwrap();
+
+        assert_eq!(save.header.format_version, SAVE_FORMAT_VERSION);
+        assert_eq!(save.header.game_version, "unknown");
+        assert_eq!(save.dungeon.checksum(), dungeon.checksum());
+
+        // and the player can still be found
+        let player_id = save.dungeon.player_mob_id();
+        assert!(save.dungeon.mob_position(player_id).is_some());
+    }
+
+    #[test]
+    fn saves_from_newer_versions_are_refused() {
+        let mut save = SaveFile::new(dungeon(), "99.0.0");
+        save.header.format_version = SAVE_FORMAT_VERSION + 1;
+        let data = save.to_bytes(Encoding::Ron).unwrap();
+
+        let err = SaveFile::from_bytes(&data).err().unwrap();
+        assert!(err.contains("too new"), "{}", err);
+        assert!(err.contains("99.0.0"), "{}", err);
+    }
+
+    #[test]
+    fn write_atomically_replaces_the_whole_file() {
+        let path = std::env::temp_dir().join("th-test-write-atomically");
+        let path = path.to_string_lossy();
+
+        fs::write(&*path, b"an old, and rather longer, save").unwrap();
+        write_atomically(&path, b"a new save").unwrap();
+
+        assert_eq!(fs::read(&*path).unwrap(), b"a new save");
+        assert!(fs::metadata(format!("{}.tmp", path)).is_err());
+        fs::remove_file(&*path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+// drawing to (and reading input from) a real terminal, with termbox
+//
+// th and mv both run in a terminal, and share this. it's behind the
+// "termbox" feature, so that anything else using lib (e.g. mg) doesn't
+// have to build termbox.
+
+use crate::keys::*;
+use crate::palette::*;
+use crate::render::*;
+use termbox_sys::*;
+
+#[derive(Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum EventType {
+    Mouse(MouseButton, i32, i32), // button, x, y
+    Resize(i32, i32), // w, h
+    Key(KeyPress),
+}
+
+impl EventType {
+    pub fn from_rawevent(ev: &RawEvent) -> Result<EventType, String> {
+        match ev.etype {
+            TB_EVENT_KEY => {
+                let key = if ev.key > 0 {
+                    key_from_tb(ev.key)
+                        .ok_or(format!("unknown key: {}", ev.key))?
+                } else if ev.ch > 0 {
+                    Key::Char(std::char::from_u32(ev.ch).unwrap())
+                } else {
+                    return Err("event type is TB_EVENT_KEY; but both key and ch are null"
+                        .to_string());
+                };
+
+                Ok(EventType::Key(KeyPress {
+                    key: key,
+                    alt: ev.emod & TB_MOD_ALT != 0,
+                }))
+            },
+            TB_EVENT_RESIZE => Ok(EventType::Resize(ev.w, ev.h)),
+            TB_EVENT_MOUSE => {
+                let button = mouse_from_tb(ev.key)
+                    .ok_or(format!("unknown mouse button: {}", ev.key))?;
+                Ok(EventType::Mouse(button, ev.x, ev.y))
+            },
+            _ => Err(format!("invalid event type: {}", ev.etype)),
+        }
+    }
+}
+
+// turn one of termbox's key codes into a Key. some control keys
+// are indistinguishable from other keys (ctrl-h is backspace,
+// ctrl-i is tab, and ctrl-m is enter), and those win.
+fn key_from_tb(key: u16) -> Option<Key> {
+    match key {
+        TB_KEY_F12..=TB_KEY_F1 => Some(Key::F((TB_KEY_F1 - key) as u8 + 1)),
+        TB_KEY_INSERT => Some(Key::Insert),
+        TB_KEY_DELETE => Some(Key::Delete),
+        TB_KEY_HOME => Some(Key::Home),
+        TB_KEY_END => Some(Key::End),
+        TB_KEY_PGUP => Some(Key::PageUp),
+        TB_KEY_PGDN => Some(Key::PageDown),
+        TB_KEY_ARROW_UP => Some(Key::Up),
+        TB_KEY_ARROW_DOWN => Some(Key::Down),
+        TB_KEY_ARROW_LEFT => Some(Key::Left),
+        TB_KEY_ARROW_RIGHT => Some(Key::Right),
+        TB_KEY_BACKSPACE | TB_KEY_BACKSPACE2 => Some(Key::Backspace),
+        TB_KEY_TAB => Some(Key::Tab),
+        TB_KEY_ENTER => Some(Key::Enter),
+        TB_KEY_ESC => Some(Key::Escape),
+        TB_KEY_SPACE => Some(Key::Space),
+        TB_KEY_CTRL_A..=TB_KEY_CTRL_Z =>
+            Some(Key::Ctrl((b'a' + (key - TB_KEY_CTRL_A) as u8) as char)),
+        _ => None,
+    }
+}
+
+fn mouse_from_tb(key: u16) -> Option<MouseButton> {
+    match key {
+        TB_KEY_MOUSE_LEFT => Some(MouseButton::Left),
+        TB_KEY_MOUSE_RIGHT => Some(MouseButton::Right),
+        TB_KEY_MOUSE_MIDDLE => Some(MouseButton::Middle),
+        TB_KEY_MOUSE_RELEASE => Some(MouseButton::Release),
+        TB_KEY_MOUSE_WHEEL_UP => Some(MouseButton::WheelUp),
+        TB_KEY_MOUSE_WHEEL_DOWN => Some(MouseButton::WheelDown),
+        _ => None,
+    }
+}
+
+// the real screen
+pub struct Termbox {
+    palette: Palette,
+}
+
+impl Termbox {
+    pub fn init(palette: Palette) -> Termbox {
+        match unsafe { tb_init() } {
+            TB_EFAILED_TO_OPEN_TTY => {
+                eprintln!("error: could not open terminal");
+                std::process::exit(1);
+            },
+            TB_EUNSUPPORTED_TERMINAL => {
+                eprintln!("error: unsupported terminal");
+                eprintln!("hint: try using another terminal (such as xterm or alacritty)");
+                std::process::exit(1);
+            },
+            TB_EPIPE_TRAP_ERROR => {
+                eprintln!("error: could not initialize screen");
+                std::process::exit(1);
+            },
+            _ => (),
+        }
+
+        let mode = match palette {
+            Palette::TrueColor => TB_OUTPUT_TRUECOLOR,
+            Palette::Colors256 => TB_OUTPUT_256,
+            Palette::Colors16 | Palette::Colors8 => TB_OUTPUT_NORMAL,
+        };
+
+        unsafe {
+            tb_select_input_mode(TB_INPUT_ESC | TB_INPUT_MOUSE);
+            tb_select_output_mode(mode);
+            tb_set_clear_attributes(TB_WHITE, TB_BLACK);
+            tb_clear();
+        }
+
+        Termbox { palette: palette }
+    }
+
+    // a colour, as termbox wants it in the current output mode
+    fn tb_color(&self, rgb: u32, bg: bool) -> u32 {
+        let index = self.palette.index(rgb);
+        match self.palette {
+            Palette::TrueColor | Palette::Colors256 => index,
+
+            // the basic colours start at TB_BLACK, and the bright ones
+            // are the basic ones in bold, which only works for the
+            // foreground
+            Palette::Colors16 | Palette::Colors8 => {
+                let color = (index % 8) + TB_BLACK as u32;
+                if index >= 8 && !bg {
+                    color | TB_BOLD as u32
+                } else {
+                    color
+                }
+            },
+        }
+    }
+}
+
+impl Renderer for Termbox {
+    fn width(&self) -> i32 {
+        unsafe { tb_width() }
+    }
+
+    fn height(&self) -> i32 {
+        unsafe { tb_height() }
+    }
+
+    fn clear(&mut self) {
+        unsafe { tb_clear(); }
+    }
+
+    fn put_cell(&mut self, x: i32, y: i32, cell: Cell) {
+        unsafe {
+            tb_put_cell(x, y, &RawCell {
+                ch: cell.ch as u32,
+                fg: self.tb_color(cell.fg, false),
+                bg: self.tb_color(cell.bg, true),
+            });
+        }
+    }
+
+    fn present(&mut self) {
+        unsafe { tb_present(); }
+    }
+
+    fn close(&mut self) {
+        unsafe { tb_shutdown(); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_in_each_output_mode() {
+        let true_color = Termbox { palette: Palette::TrueColor };
+        assert_eq!(true_color.tb_color(0x5ad0fa, false), 0x5ad0fa);
+
+        let colors_256 = Termbox { palette: Palette::Colors256 };
+        assert_eq!(colors_256.tb_color(0xff0000, false), 196);
+
+        // bright colours are bold, except in the background
+        let colors_16 = Termbox { palette: Palette::Colors16 };
+        assert_eq!(colors_16.tb_color(0xff0000, false), (TB_RED | TB_BOLD) as u32);
+        assert_eq!(colors_16.tb_color(0xff0000, true), TB_RED as u32);
+
+        let colors_8 = Termbox { palette: Palette::Colors8 };
+        assert_eq!(colors_8.tb_color(0xff0000, false), TB_RED as u32);
+        assert_eq!(colors_8.tb_color(0x000000, true), TB_BLACK as u32);
+    }
+}
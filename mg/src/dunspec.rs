@@ -4,6 +4,7 @@ use crate::maze::*;
 use crate::mineral_placement::*;
 use crate::mob_placement::*;
 use crate::randrm::*;
+use lib::encoding::*;
 use serde::Deserialize;
 use std::vec::Vec;
 
@@ -17,6 +18,10 @@ pub enum MapgenAlgorithm {
 
 #[derive(Debug, Deserialize)]
 pub struct LayerSpecification {
+    // the name given to every level of the layer
+    #[serde(default)]
+    pub name: Option<String>,
+
     pub levels: usize,
     pub dimensions: (usize, usize),   // (width, height)
     pub composition: MineralPlacementOptions,
@@ -27,5 +32,15 @@ pub struct LayerSpecification {
 #[derive(Debug, Deserialize)]
 pub struct DungeonSpecification {
     pub world_name: String,
+
+    // the seed to generate the world from; a random
+    // one is picked if none is given
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    // how to write the map out; RON unless told otherwise
+    #[serde(default)]
+    pub encoding: Encoding,
+
     pub layers: Vec<LayerSpecification>,
 }
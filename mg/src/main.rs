@@ -17,11 +17,13 @@ use lib::dungeon::*;
 use lib::id::*;
 use lib::material::*;
 use lib::mob::*;
+use lib::save::*;
 use crate::mineral_placement::*;
 use crate::mob_placement::*;
 use crate::randrm::*;
 
-use std::collections::HashMap;
+use rand::{SeedableRng, rngs::StdRng};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::{fs, fs::File};
 use ron::de::from_reader;
@@ -30,9 +32,11 @@ use walkdir::WalkDir;
 const PLAYER_MOB: &str = "elf";
 
 fn main() {
-    let mut rng = rand::thread_rng();
-    let mut materials:   HashMap<String, MaterialInfo> = HashMap::new();
-    let mut mobs:        HashMap<String, MobTemplate>  = HashMap::new();
+    // the info files are kept sorted by id, so that they're handed to
+    // the placers (and use up the rng) in the same order every time,
+    // and a seed always generates the same world
+    let mut materials:   BTreeMap<String, MaterialInfo> = BTreeMap::new();
+    let mut mobs:        BTreeMap<String, MobTemplate>  = BTreeMap::new();
     let mut dungeons_s1: Vec<DungeonS1> = Vec::new();
     let mut dungeons_s2: Vec<DungeonS2> = Vec::new();
     let mut mob_table:   HashMap<u64, Mob> = HashMap::new();
@@ -47,7 +51,7 @@ fn main() {
 
     // load and parse material info files
     // TODO: do not hardcode paths
-    fn load_info_files<T>(arg0: &str, path: &str, accm: &mut HashMap<String, T>) -> Result<(), Box<dyn Error>>
+    fn load_info_files<T>(arg0: &str, path: &str, accm: &mut BTreeMap<String, T>) -> Result<(), Box<dyn Error>>
     where
         T: for<'a> serde::Deserialize<'a> + Id + Clone
     {
@@ -94,6 +98,11 @@ fn main() {
         },
     };
 
+    // seed the generator ourselves, so that the seed
+    // can be kept along with the dungeon
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+
     for layer in &config.layers {
         for _level in 0..layer.levels {
             let mut map = DungeonS1::new(layer.dimensions.0,
@@ -121,6 +130,7 @@ fn main() {
 
             // decide minerals and mobs
             let mut new_map = DungeonS2::from_dungeon_s1(&map);
+            new_map.name = layer.name.clone().unwrap_or_default();
             MineralPlacer::new(&mut new_map, layer.composition, &mut rng)
                 .generate(materials.values().cloned().collect());
             let new_mobs = MobPlacer::new(&mut new_map, layer.inhabitants.clone(), &mut rng)
@@ -133,8 +143,11 @@ fn main() {
 
     // ensure that the info file isn't missing
     assert!(mobs.contains_key(PLAYER_MOB));
-    let dungeon = Dungeon::from_dungeon_s2(config.world_name, &mut dungeons_s2,
+    let mut dungeon = Dungeon::from_dungeon_s2(config.world_name, &mut dungeons_s2,
         &mut rng, &mobs[PLAYER_MOB], mob_table);
+    dungeon.seed = seed;
 
-    fs::write("map.ron", ron::to_string(&dungeon).unwrap().as_bytes()).unwrap();
+    let data = SaveFile::dungeon_to_bytes(&dungeon, env!("CARGO_PKG_VERSION"),
+        config.encoding).unwrap();
+    fs::write(format!("map.{}", config.encoding.extension()), data).unwrap();
 }
@@ -1,18 +1,39 @@
 use lib::dirs::*;
 use lib::math::*;
+use lib::keys;
+use lib::keys::*;
 use crate::state::*;
+use serde::Deserialize;
 use termbox_sys::*;
-use std::collections::HashMap;
 
-#[derive(Copy, Clone)]
+// where the default keybindings are, if the
+// user hasn't set up their own
+const DEFAULT_KEYBINDINGS: &str = "../dat/keys/mv.ron";
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
 pub enum KeybindingAction {
     LevelUp,
     LevelDown,
     CursorMove(Direction),
     CursorMoveFast(Direction),
+    Help,
     Quit,
 }
 
+impl Action for KeybindingAction {
+    fn description(&self) -> String {
+        match self {
+            KeybindingAction::LevelUp => "go up a level".to_string(),
+            KeybindingAction::LevelDown => "go down a level".to_string(),
+            KeybindingAction::CursorMove(d) => format!("move cursor {}", d.name()),
+            KeybindingAction::CursorMoveFast(d) =>
+                format!("move cursor {} 8 tiles", d.name()),
+            KeybindingAction::Help => "show all keybindings".to_string(),
+            KeybindingAction::Quit => "quit".to_string(),
+        }
+    }
+}
+
 impl KeybindingAction {
     pub fn execute(&self, st: &mut State) -> Result<(), String> {
         match self {
@@ -47,179 +68,16 @@ impl KeybindingAction {
             KeybindingAction::LevelDown => st.level = clamp(st.level + 1, 0,
                 st.dungeon.levels.len() - 1),
             KeybindingAction::LevelUp => st.level = st.level.saturating_sub(1),
+            KeybindingAction::Help => st.show_help = true,
         }
 
         Ok(())
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-#[derive(PartialEq, Eq, Hash)]
-pub enum EventType {
-    Mouse(i32, i32), // x, y
-    Resize(i32, i32), // w, h
-    Character(char),
-    Key(u16), // TODO: key enum
-}
-
-impl EventType {
-    pub fn from_rawevent(ev: &RawEvent) -> Result<EventType, String> {
-        match ev.etype {
-            TB_EVENT_KEY => {
-                if ev.key > 0 {
-                    Ok(EventType::Key(ev.key))
-                } else if ev.ch > 0 {
-                    let ch = std::char::from_u32(ev.ch).unwrap();
-                    Ok(EventType::Character(ch))
-                } else {
-                    Err("event type is TB_EVENT_KEY; but both key and ch are null"
-                        .to_string())
-                }
-            },
-            TB_EVENT_RESIZE => Ok(EventType::Resize(ev.w, ev.h)),
-            TB_EVENT_MOUSE => Ok(EventType::Mouse(ev.x, ev.y)),
-            _ => Err(format!("invalid event type: {}", ev.etype)),
-        }
-    }
-}
-
-pub struct Keybinding {
-    trigger: EventType,
-    action: KeybindingAction,
-}
-
-pub struct Keybindings(Vec<Keybinding>);
-
-impl Keybindings {
-    // default keybindings
-    pub fn new() -> Keybindings {
-        let bindings = vec![
-            Keybinding {
-                trigger: EventType::Character('h'),
-                action: KeybindingAction::CursorMove(Direction::West),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('j'),
-                action: KeybindingAction::CursorMove(Direction::South),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('k'),
-                action: KeybindingAction::CursorMove(Direction::North),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('l'),
-                action: KeybindingAction::CursorMove(Direction::East),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('y'),
-                action: KeybindingAction::CursorMove(Direction::NorthWest),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('u'),
-                action: KeybindingAction::CursorMove(Direction::NorthEast),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('b'),
-                action: KeybindingAction::CursorMove(Direction::SouthWest),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('n'),
-                action: KeybindingAction::CursorMove(Direction::SouthEast),
-            },
-
-            // move keys quickly
-            Keybinding {
-                trigger: EventType::Character('H'),
-                action: KeybindingAction::CursorMoveFast(Direction::West),
-            },
+pub type Keybindings = keys::Keybindings<KeybindingAction>;
 
-            Keybinding {
-                trigger: EventType::Character('J'),
-                action: KeybindingAction::CursorMoveFast(Direction::South),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('K'),
-                action: KeybindingAction::CursorMoveFast(Direction::North),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('L'),
-                action: KeybindingAction::CursorMoveFast(Direction::East),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('Y'),
-                action: KeybindingAction::CursorMoveFast(Direction::NorthWest),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('U'),
-                action: KeybindingAction::CursorMoveFast(Direction::NorthEast),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('B'),
-                action: KeybindingAction::CursorMoveFast(Direction::SouthWest),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('N'),
-                action: KeybindingAction::CursorMoveFast(Direction::SouthEast),
-            },
-
-            // level down
-            Keybinding {
-                trigger: EventType::Character('>'),
-                action: KeybindingAction::LevelDown,
-            },
-
-            Keybinding {
-                trigger: EventType::Character('<'),
-                action: KeybindingAction::LevelUp,
-            },
-
-            // quit
-            Keybinding {
-                trigger: EventType::Key(TB_KEY_CTRL_C),
-                action: KeybindingAction::Quit,
-            },
-
-            Keybinding {
-                trigger: EventType::Character('q'),
-                action: KeybindingAction::Quit,
-            },
-        ];
-
-        Keybindings(bindings)
-    }
-
-    pub fn as_table(&self) -> HashMap<EventType, KeybindingAction> {
-        let mut table = HashMap::new();
-        for kbd in &self.0 {
-            if !table.contains_key(&kbd.trigger) {
-                table.entry(kbd.trigger).or_insert(kbd.action);
-            } else {
-                *table.get_mut(&kbd.trigger).unwrap() = kbd.action;
-            }
-        }
-        table
-    }
-
-    pub fn handle_ev(&self, ev: EventType, st: &mut State) -> Result<(), String> {
-        let kbds = self.as_table();
-
-        if kbds.contains_key(&ev) {
-            kbds[&ev].execute(st)?;
-        }
-
-        Ok(())
-    }
+// the user's own keybindings, or the default ones
+pub fn load_keybindings() -> Result<Keybindings, String> {
+    Keybindings::load("mv", DEFAULT_KEYBINDINGS)
 }
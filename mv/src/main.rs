@@ -1,6 +1,5 @@
 mod kbd;
 mod state;
-mod utils;
 
 use crate::state::*;
 use crate::kbd::*;
@@ -9,10 +8,16 @@ use lib::dungeon::*;
 use lib::dun_s1::*;
 use lib::dun_s2::*;
 use lib::info_files::*;
+use lib::keys::MouseButton;
 use lib::material::*;
-use ron::de::from_reader;
+use lib::encoding::*;
+use lib::mob::*;
+use lib::render::*;
+use lib::save::*;
+use lib::termbox::*;
 use std::collections::HashMap;
-use std::fs::File;
+use std::error::Error;
+use std::fs;
 use termbox_sys::*;
 
 fn main() {
@@ -29,16 +34,40 @@ fn main() {
     }));
 
     // check arguments
-    let args = std::env::args().collect::<Vec<String>>();
+    let mut args = std::env::args().collect::<Vec<String>>();
+    let options = match DisplayOptions::from_args(&mut args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            std::process::exit(1);
+        },
+    };
+
     if args.len() < 2 {
         eprintln!("{}: need dungeon file.", args[0]);
-        eprintln!("usage: {} <file>", args[0]);
+        eprintln!("usage: {} [--ascii] [--colors <true|256|16|8>] <file>", args[0]);
+        eprintln!("       {} --convert <ron|binary|compressed> <file> <output>",
+            args[0]);
         std::process::exit(1);
     }
 
+    if args[1] == "--convert" {
+        if args.len() < 5 {
+            eprintln!("usage: {} --convert <ron|binary|compressed> <file> <output>",
+                args[0]);
+            std::process::exit(1);
+        }
+
+        if let Err(e) = convert(&args[2], &args[3], &args[4]) {
+            println!("{}: \"{}\": {}", args[0], args[3], e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // try to load map
     let input_path = &args[1];
-    let fmap = match File::open(input_path) {
+    let data = match fs::read(input_path) {
         Ok(d) => d,
         Err(e) => {
             println!("{}: \"{}\": {}", args[0], input_path, e);
@@ -46,9 +75,9 @@ fn main() {
         },
     };
 
-    // parse map
-    let map: Dungeon = match from_reader(fmap) {
-        Ok(x) => x,
+    // parse map (or save)
+    let map: Dungeon = match SaveFile::from_bytes(&data) {
+        Ok(x) => x.dungeon,
         Err(e) => {
             println!("{}: failed to load map: {}", args[0], e);
             std::process::exit(1);
@@ -57,38 +86,21 @@ fn main() {
 
     let materials = load_info_files("../dat/mats/").unwrap();
 
-    utils::setup_tb();
-    let mut st: State = State::new(map);
-
-    draw_map(&st.dungeon, st.screen_width / 2, st.screen_height - 5,
-        &materials, st.current_x, st.current_y, st.level);
-    draw_desc(&st.dungeon, st.screen_width, st.screen_height,
-        &materials, st.current_x, st.current_y, st.level);
-
-    // write keybindings
-    let kbds: Vec<(&str, &str)> = vec![
-        ("<>",   "change level"),
-        ("hjkl", "move cursor west/south/north/east"),
-        ("HJKL", "like hjkl, but move 8 tiles at a time"),
-        ("q",    "quit"),
-    ];
-
-    for kbd_i in 0..(kbds.len()) {
-        let kbd = kbds[kbd_i];
-        let row = st.screen_height - 5 + (kbd_i as i32);
-        let mut col: i32 = 0;
-
-        col = tb_put_string(st.screen_width / 2, st.screen_height, col, row,
-            kbd.0, 0x000000, 0xffffff, false).1;
-        if col < 5 { col += 5 - col; }
-        tb_put_string(st.screen_width / 2, st.screen_height, col, row,
-            kbd.1, 0xffffff, 0x000000, false).1;
-    }
+    let kbd = match load_keybindings() {
+        Ok(k) => k,
+        Err(e) => {
+            println!("{}: failed to load keybindings: {}", args[0], e);
+            std::process::exit(1);
+        },
+    };
+    let keybinds = kbd.as_table();
+    let help = kbd.help();
 
-    unsafe { tb_present(); }
+    let mut r = Termbox::init(options.palette);
+    let mut st: State = State::new(map, r.width(), r.height());
 
-    // setup default keybindings
-    let kbd = Keybindings::new();
+    draw(&mut r, &st, options.glyphs, &materials, &help);
+    r.present();
 
     // main loop
     loop {
@@ -96,43 +108,119 @@ fn main() {
         let t = unsafe { tb_poll_event(&mut raw_ev) };
 
         if t == -1 {
-            unsafe { tb_shutdown(); }
+            r.close();
             eprintln!("error: fatal termbox error");
             std::process::exit(1);
         }
 
-        if t == (TB_EVENT_KEY as i32) {
-            let ev = EventType::from_rawevent(&raw_ev)
-                .unwrap();
+        if t == (TB_EVENT_KEY as i32) || t == (TB_EVENT_MOUSE as i32) {
+            // keys termbox knows about but we don't (e.g. ctrl-\)
+            // are ignored, rather than crashing the viewer
+            let ev = match EventType::from_rawevent(&raw_ev) {
+                Ok(ev) => ev,
+                Err(_) => continue,
+            };
             match ev {
-                EventType::Character(_)
-                | EventType::Key(_) => {
-                    match kbd.handle_ev(ev, &mut st) {
-                        Ok(_) => (),
-                        Err(_) => break,
+                // any key closes the help
+                EventType::Key(_) if st.show_help => st.show_help = false,
+
+                // clicking on the map moves the cursor there
+                EventType::Mouse(MouseButton::Left, x, y) if !st.show_help => {
+                    if let Some((tile_y, tile_x)) = tile_at(&st, x, y) {
+                        st.current_y = tile_y;
+                        st.current_x = tile_x;
+                    }
+                },
+                EventType::Key(k) => {
+                    if let Some(action) = keybinds.get(&k) {
+                        if action.execute(&mut st).is_err() {
+                            break;
+                        }
                     }
                 },
                 EventType::Resize(w, h) => {
                     st.screen_height = h;
                     st.screen_width = w;
-                    unsafe { tb_present(); }
+                    r.present();
                 },
                 _ => (),
             }
 
-            draw_map(&st.dungeon, st.screen_width / 2, st.screen_height - 5,
-                &materials, st.current_x, st.current_y, st.level);
-            draw_desc(&st.dungeon, st.screen_width, st.screen_height,
-                &materials, st.current_x, st.current_y, st.level);
-            unsafe { tb_present(); }
+            draw(&mut r, &st, options.glyphs, &materials, &help);
+            r.present();
         }
     }
 
-    unsafe { tb_shutdown(); }
+    r.close();
+}
+
+// re-encode a map or save, e.g. to RON so that it can be read
+fn convert(encoding: &str, input_path: &str, output_path: &str)
+    -> Result<(), Box<dyn Error>>
+{
+    let encoding = Encoding::from_name(encoding)
+        .ok_or(format!("unknown encoding \"{}\"", encoding))?;
+    let save = SaveFile::from_bytes(&fs::read(input_path)?)?;
+    fs::write(output_path, save.to_bytes(encoding)?)?;
+    Ok(())
+}
+
+fn draw(r: &mut dyn Renderer, st: &State, glyphs: Glyphs,
+    materials: &HashMap<String, MaterialInfo>, help: &[(String, String)])
+{
+    r.clear();
+
+    if st.show_help {
+        draw_help(r, st.screen_width, st.screen_height, help);
+        return;
+    }
+
+    let (map_width, map_height) = map_size(st);
+    draw_map(r, &st.dungeon, map_width, map_height,
+        glyphs, materials, st.current_x, st.current_y, st.level);
+    draw_desc(r, &st.dungeon, st.screen_width, st.screen_height,
+        glyphs, materials, st.current_x, st.current_y, st.level);
+    draw_keybindings(r, st.screen_width, st.screen_height, help);
+}
+
+// as many of the keybindings as fit under the map; the
+// first ones bound are the ones that get shown
+fn draw_keybindings(r: &mut dyn Renderer, max_x: i32, max_y: i32,
+    help: &[(String, String)])
+{
+    for (i, (keys, description)) in help.iter().take(5).enumerate() {
+        let row = max_y - 5 + (i as i32);
+        let mut col: i32 = 0;
+
+        col = r.put_string(max_x / 2, max_y, col, row,
+            keys, 0x000000, 0xffffff, false).1;
+        if col < 5 { col += 5 - col; }
+        r.put_string(max_x / 2, max_y, col, row,
+            description, 0xffffff, 0x000000, false);
+    }
+}
+
+fn draw_help(r: &mut dyn Renderer, max_x: i32, max_y: i32,
+    help: &[(String, String)])
+{
+    r.put_string(max_x, max_y, 0, 0, "Keybindings (press any key to close)",
+        0x000000, 0xffffff, false);
+
+    let width = help.iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (i, (keys, description)) in help.iter().enumerate() {
+        let line = format!("{:<width$}  {}", keys, description, width = width);
+        r.put_string(max_x, max_y, 0, i as i32 + 1, &line,
+            0xffffff, 0x000000, false);
+    }
 }
 
 pub fn draw_desc(
-    map: &Dungeon, max_x: i32, max_y: i32,
+    r: &mut dyn Renderer,
+    map: &Dungeon, max_x: i32, max_y: i32, glyphs: Glyphs,
     materials: &HashMap<String, MaterialInfo>,
     cur_x: usize, cur_y: usize, lvl: usize,
 ) {
@@ -142,34 +230,64 @@ pub fn draw_desc(
 
     // draw tile
     let tile = &map.levels[lvl].d[cur_y][cur_x];
-    let cell = tile_as_cell(tile, materials);
-    unsafe { tb_put_cell(col, row, &cell); }
+    let cell = tile_as_cell(tile, glyphs, materials, &map.mobs);
+    r.put_cell(col, row, cell);
     col += 2;
 
     // draw coordinates
     let dim_str = format!("(row {}/{}, col {}/{}, lvl {}/{})",
         cur_y + 1, &map.levels[lvl].height, cur_x + 1, &map.levels[lvl].width,
         lvl + 1, &map.levels.len());
-    tb_put_string(max_x, max_y, col, row, &dim_str, 0xffffff, 0x000000, false);
+    r.put_string(max_x, max_y, col, row, &dim_str, 0xffffff, 0x000000, false);
     row += 2;
     col = startx;
 
     // draw name of material
     let material = &materials[&tile.tile_material];
-    tb_put_string(max_x, max_y, col, row, &material.name,
+    r.put_string(max_x, max_y, col, row, &material.name,
         0xffffff, 0x000000, false);
 }
 
+// how much of the screen the map takes up
+fn map_size(st: &State) -> (i32, i32) {
+    (st.screen_width / 2, st.screen_height - 5)
+}
+
+// the tile at the top left of the map, which is centred on the cursor
+fn map_origin(max_x: i32, max_y: i32, cur_x: usize, cur_y: usize) -> (isize, isize) {
+    ((cur_y as isize) - ((max_y as isize) / 2),
+        (cur_x as isize) - ((max_x as isize) / 2))
+}
+
+// the tile drawn at a spot on the screen, if the map's there
+fn tile_at(st: &State, x: i32, y: i32) -> Option<(usize, usize)> {
+    let (max_x, max_y) = map_size(st);
+    if x < 0 || y < 0 || x >= max_x || y >= max_y {
+        return None;
+    }
+
+    let (top, left) = map_origin(max_x, max_y, st.current_x, st.current_y);
+    let (tile_y, tile_x) = (top + y as isize, left + x as isize);
+
+    let level = &st.dungeon.levels[st.level];
+    if tile_y < 0 || tile_x < 0 ||
+        tile_y as usize >= level.height || tile_x as usize >= level.width {
+            return None;
+    }
+
+    Some((tile_y as usize, tile_x as usize))
+}
+
 pub fn draw_map(
-    map: &Dungeon, max_x: i32, max_y: i32,
+    r: &mut dyn Renderer,
+    map: &Dungeon, max_x: i32, max_y: i32, glyphs: Glyphs,
     materials: &HashMap<String, MaterialInfo>,
     cur_x: usize, cur_y: usize, lvl: usize
 ) {
     let level  = &map.levels[lvl];
 
-    let starty = (cur_y as isize) - ((max_y as isize) / 2);
+    let (starty, startx) = map_origin(max_x, max_y, cur_x, cur_y);
     let endy   = (cur_y + ((max_y as usize) / 2)) as isize;
-    let startx = (cur_x as isize) - ((max_x as isize) / 2);
     let endx   = (cur_x + ((max_x as usize) / 2)) as isize;
 
     // screen position
@@ -181,10 +299,7 @@ pub fn draw_map(
             // if out of bounds of the map, just draw a black tile
             if (y < 0 || x < 0) ||
                 (y as usize >= level.height || x  as usize >= level.width) {
-                    unsafe {
-                        tb_change_cell(xctr as i32, yctr as i32,
-                            ' ' as u32, 0xffffff, 0x000000);
-                    }
+                    r.put_cell(xctr as i32, yctr as i32, Cell::blank());
 
                     if xctr >= max_x as usize { break; }
                     else { xctr += 1; }
@@ -192,11 +307,11 @@ pub fn draw_map(
             }
 
             let mut cell = tile_as_cell(&level.d[y as usize][x as usize],
-                materials);
+                glyphs, materials, &map.mobs);
             if x as usize == cur_x && y as usize == cur_y {
                 cell.bg = Color::new(200, 200, 0, 0).as_u32();
             }
-            unsafe { tb_put_cell(xctr as i32, yctr as i32, &cell); }
+            r.put_cell(xctr as i32, yctr as i32, cell);
 
             if xctr >= max_x as usize { break; }
             else { xctr += 1; }
@@ -209,7 +324,9 @@ pub fn draw_map(
     }
 }
 
-fn tile_as_cell(tile: &DungeonTile, materials: &HashMap<String, MaterialInfo>) -> RawCell {
+fn tile_as_cell(tile: &DungeonTile, glyphs: Glyphs,
+    materials: &HashMap<String, MaterialInfo>, mobs: &HashMap<u64, Mob>) -> Cell
+{
     let tile_material = &materials[&tile.tile_material];
     let mut bg = tile_material.color_bg;
     let mut fg = tile_material.color_fg;
@@ -218,7 +335,7 @@ fn tile_as_cell(tile: &DungeonTile, materials: &HashMap<String, MaterialInfo>) -
     match tile.tiletype {
         TileType::Debug
         | TileType::Wall  => {
-            glyph = tile_material.block_glyph;
+            glyph = glyphs.block(tile_material);
         },
         TileType::Floor => {
             glyph = ' ';
@@ -232,9 +349,9 @@ fn tile_as_cell(tile: &DungeonTile, materials: &HashMap<String, MaterialInfo>) -
         },
     }
 
-    if let Some(mob) = &tile.mobs {
+    if let Some(mob) = tile.mobs.and_then(|id| mobs.get(&id)) {
         bg = Color::new(0, 0, 0, 0);
-        glyph = mob.unicode_glyph;
+        glyph = glyphs.mob(mob);
         if let Some(mob_fg) = mob.glyph_fg {
             fg = mob_fg;
         } else {
@@ -242,65 +359,5 @@ fn tile_as_cell(tile: &DungeonTile, materials: &HashMap<String, MaterialInfo>) -
         }
     }
 
-    RawCell {
-        ch: glyph as u32,
-        fg: fg.as_u32(),
-        bg: bg.as_u32(),
-    }
-}
-
-fn tb_put_string(
-    max_x: i32, max_y: i32,
-    col: i32, row: i32,
-    str: &str,
-    fg: u32, bg: u32,
-    wrap: bool
-) -> (i32, i32) {
-    let mut ccol = col;
-    let mut crow = row;
-    for c in str.chars() {
-        unsafe {
-            tb_put_cell(ccol, crow, &RawCell {
-                ch: c as u32,
-                fg: fg, bg: bg
-            });
-        }
-
-        if (ccol + 1) == (max_x - 1) {
-            if wrap && crow + 1 != max_y {
-                crow += 1;
-                ccol = col;
-            } else {
-                let dot = RawCell {
-                    ch: '.' as u32,
-                    fg: fg, bg: bg,
-                };
-
-                // draw some nice ellipses
-                unsafe {
-                    tb_put_cell(ccol - 2, crow, &dot);
-                    tb_put_cell(ccol - 1, crow, &dot);
-                    tb_put_cell(ccol - 0, crow, &dot);
-                }
-
-                break;
-            }
-        } else {
-            ccol += 1;
-        }
-    }
-
-    // clear to the end of the line
-    let clear_cell = RawCell {
-        ch: ' ' as u32,
-        fg: 0xffffff, bg: 0x000000,
-    };
-
-    for ncol in ccol..max_x {
-        unsafe {
-            tb_put_cell(ncol, row, &clear_cell);
-        }
-    }
-
-    (crow, ccol)
+    Cell::new(glyph, fg.as_u32(), bg.as_u32())
 }
@@ -1,4 +1,3 @@
-use termbox_sys::*;
 use lib::dungeon::Dungeon;
 
 pub struct State {
@@ -8,23 +7,20 @@ pub struct State {
     pub current_x: usize,
     pub current_y: usize,
     pub level: usize,
+
+    // whether all the keybindings are being shown
+    pub show_help: bool,
 }
 
 impl State {
-    pub fn new(dungeon: Dungeon) -> State {
-        let (screen_width, screen_height);
-
-        unsafe {
-            screen_width = tb_width();
-            screen_height = tb_height();
-        }
-
+    pub fn new(dungeon: Dungeon, screen_width: i32, screen_height: i32) -> State {
         State {
             screen_width: screen_width,
             screen_height: screen_height,
             dungeon: dungeon,
             current_x: 0, current_y: 0,
             level: 0,
+            show_help: false,
         }
     }
 }
@@ -0,0 +1,97 @@
+// saving the game every so often without being asked, and as a
+// last resort when th crashes, so that a crash doesn't lose the
+// whole session
+//
+// autosaves go in their own file next to the save. saving by hand,
+// quitting, or dying all remove it, so if there's still one there
+// when th starts, the last game didn't end cleanly and the player
+// is asked whether they want to carry on from it.
+
+use crate::state::*;
+use lib::message::*;
+use lib::priority::*;
+use lib::save::*;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+// how many turns go by between autosaves
+const AUTOSAVE_TURNS: u64 = 100;
+
+pub fn autosave_path_for(map_path: &str) -> String {
+    format!("{}.autosave", map_path)
+}
+
+impl State {
+    pub fn autosave_path(&self) -> String {
+        autosave_path_for(&self.map_path)
+    }
+
+    // autosave, if it's been long enough since the last time
+    pub fn maybe_autosave(&mut self) {
+        let turn = self.dungeon.turn();
+        if turn < self.last_autosave + AUTOSAVE_TURNS {
+            return;
+        }
+
+        self.last_autosave = turn;
+        if let Err(e) = self.write_autosave() {
+            self.dungeon.messages.push(Message::new(
+                &format!("Autosave failed: {}", e), Priority::High));
+        }
+
+        // keep the replay about as up to date, in case
+        // th's killed before it can write it out
+        let _ = self.write_replay();
+    }
+
+    pub fn write_autosave(&self) -> Result<(), Box<dyn Error>> {
+        let data = SaveFile::dungeon_to_bytes(&self.dungeon,
+            env!("CARGO_PKG_VERSION"), SAVE_ENCODING)?;
+        write_atomically(&self.autosave_path(), &data)?;
+        Ok(())
+    }
+
+    pub fn clear_autosave(&self) {
+        let _ = fs::remove_file(self.autosave_path());
+    }
+
+    // save whatever there is after th has crashed, returning where it
+    // went. the game may well have been halfway through changing
+    // something, but a slightly odd save beats no save at all.
+    pub fn emergency_save(&self) -> Result<String, Box<dyn Error>> {
+        self.write_autosave()?;
+        Ok(self.autosave_path())
+    }
+}
+
+// if the last game on this map didn't end cleanly, ask the player
+// whether they want to restore the autosave it left behind. this
+// happens before termbox starts, so it's a plain prompt.
+pub fn offer_restore(map_path: &str) -> bool {
+    let path = autosave_path_for(map_path);
+    if !Path::new(&path).exists() {
+        return false;
+    }
+
+    println!("the last game on {} didn't end cleanly, but it was autosaved.",
+        map_path);
+    print!("restore it? [Y/n] ");
+    let _ = io::stdout().flush();
+
+    // if there's no way to ask, leave the autosave
+    // alone for next time
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    match answer.trim().to_lowercase().as_str() {
+        "" | "y" | "yes" => true,
+        _ => {
+            let _ = fs::remove_file(&path);
+            false
+        },
+    }
+}
@@ -0,0 +1,135 @@
+// melee combat between mobs
+//
+// there aren't any hit points: a hit draws blood and leaves a wound
+// that keeps bleeding for a while (see Mob::lose_blood and Mob::wound),
+// and a mob that runs out of blood dies.
+
+use lib::message::*;
+use crate::tick::*;
+use lib::coord::*;
+use lib::items::*;
+use lib::math::*;
+use lib::mob::*;
+use lib::priority::*;
+use rand::prelude::*;
+
+// the chance out of 100 of hitting a mob that's
+// exactly as agile as the attacker
+const BASE_HIT_CHANCE: isize = 60;
+
+// millilitres of blood a hit draws for every point of the
+// attacker's strength, and for every kilogram a wielded
+// weapon weighs
+const BLOOD_PER_STRENGTH: u32 = 10;
+const BLOOD_PER_WEAPON_KG: u32 = 50;
+
+// a wound bleeds this fraction of the blood drawn
+// by the hit every turn, until it clots
+const WOUND_BLEEDING: u32 = 20;
+
+// how much blood a hit from the attacker draws
+fn hit_damage(attacker: &Mob) -> u32 {
+    let weapons = attacker.equipment.iter()
+        .filter(|(slot, _)| matches!(slot, EquipmentSlot::Hand(_)))
+        .map(|(_, item)| item.weight as u32 / 1000)
+        .sum::<u32>();
+
+    attacker.current_strength() as u32 * BLOOD_PER_STRENGTH
+        + weapons * BLOOD_PER_WEAPON_KG
+}
+
+// have one mob attack another one next to it. returns true if the
+// victim was killed.
+pub fn attack<R>(ctx: &mut Ctx<R>, lvl: usize,
+    attacker_id: u64, attacker_coords: (usize, usize),
+    victim_id: u64, victim_coords: (usize, usize)) -> bool
+where
+    R: Rng
+{
+    let st = &mut *ctx.st;
+    let mobs = ctx.mobs;
+    let player_id = st.dungeon.player_mob_id();
+    let attacker = &st.dungeon.mobs[&attacker_id];
+    let attacker_template = &mobs[&attacker.from_mob_template];
+    let victim_template = &mobs[&st.dungeon.mobs[&victim_id].from_mob_template];
+
+    let attacker_agility = attacker.current_agility() as isize;
+    let victim_agility = st.dungeon.mobs[&victim_id].current_agility() as isize;
+    let chance = clamp(BASE_HIT_CHANCE + attacker_agility - victim_agility, 5, 95);
+    let hit = ctx.rng.gen_range(0, 100) < chance;
+    let damage = hit_damage(attacker);
+
+    // whether it hurt or not, the victim won't forget this
+    let victim = st.dungeon.mobs.get_mut(&victim_id).unwrap();
+    let seen = victim.awareness() == Awareness::Alerted;
+    victim.hold_grudge(attacker_id);
+    victim.sightings.insert(attacker_id, MobSighting {
+        level: lvl, coords: Coord::from(attacker_coords), ticks_ago: 0,
+    });
+    if attacker_id == player_id {
+        victim.set_suspicion(ALERTED_AT);
+    }
+
+    let visible = player_sees(st, lvl, attacker_coords)
+        || player_sees(st, lvl, victim_coords);
+    let msg = if attacker_id == player_id {
+        let verb = if hit { "hit" } else { "miss" };
+        Some(format!("You {} the {}.", verb, victim_template.short_name))
+    } else if victim_id == player_id {
+        let verb = if hit { "hits" } else { "misses" };
+        Some(format!("The {} {} you!", attacker_template.short_name, verb))
+    } else if visible {
+        let verb = if hit { "hits" } else { "misses" };
+        Some(format!("The {} {} the {}.", attacker_template.short_name,
+            verb, victim_template.short_name))
+    } else {
+        None
+    };
+    if let Some(msg) = msg {
+        let priority = if victim_id == player_id && hit {
+            Priority::High
+        } else {
+            Priority::Normal
+        };
+        st.dungeon.messages.push(Message::new(&msg, priority));
+    }
+
+    if !hit {
+        return false;
+    }
+
+    let victim = st.dungeon.mobs.get_mut(&victim_id).unwrap();
+    let drawn = victim.lose_blood(damage);
+    victim.wound(drawn / WOUND_BLEEDING);
+    let dead = victim.blood() == 0;
+
+    // vampires get to drink whatever they spill
+    if attacker_template.vampire {
+        st.dungeon.mobs.get_mut(&attacker_id).unwrap().gain_blood(drawn);
+    }
+
+    if !dead {
+        return false;
+    }
+
+    if victim_id == player_id {
+        st.player_dies(&format!("killed by a {}", attacker_template.short_name));
+        return true;
+    }
+
+    st.dungeon.kill_mob(lvl, victim_coords, victim_template);
+
+    if attacker_id == player_id {
+        st.dungeon.player.stealth.killed(seen);
+        st.dungeon.messages.push(Message::new(
+            &format!("You kill the {}!", victim_template.short_name),
+            Priority::High));
+    } else if visible {
+        st.dungeon.messages.push(Message::new(
+            &format!("The {} kills the {}.", attacker_template.short_name,
+                victim_template.short_name),
+            Priority::Normal));
+    }
+
+    true
+}
@@ -1,124 +1,399 @@
+use crate::look::*;
+use crate::morgue::*;
 use crate::state::*;
-use crate::tb::*;
+use crate::titles::*;
 use lib::colors::*;
 use lib::coord::*;
 use lib::dun_s1::*;
 use lib::dun_s2::*;
 use lib::material::*;
 use lib::mob::*;
+use lib::priority::*;
+use lib::render::*;
 use std::collections::HashMap;
-use termbox_sys::*;
+
+// what colour a message is shown in, depending
+// on how important it is
+fn priority_color(priority: Priority) -> u32 {
+    match priority {
+        Priority::Min => 0x666666,
+        Priority::VLow => 0x888888,
+        Priority::Low => 0xbbbbbb,
+        Priority::Normal => 0xffffff,
+        Priority::High => 0xffff77,
+        Priority::VHigh => 0xffaa55,
+        Priority::Max => 0xff5555,
+    }
+}
 
 enum DisplayWindow {
     Map, Message, Detail,
 }
 
 impl DisplayWindow {
-    // get dimensions of window
+    // get dimensions of window on a screen of the given size
     // (startx, starty, endx, endy)
-    fn dimensions(&self) -> (i32, i32, i32, i32) {
+    fn dimensions(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
         match self {
             DisplayWindow::Map => {
-                (0, 0, unsafe { tb_width() } - 30, unsafe { tb_height() } - 8)
+                (0, 0, width - 30, height - 8)
             },
             DisplayWindow::Message => {
-                let (_, _, map_x, map_y) = DisplayWindow::Map.dimensions();
-                (0, map_y + 1, map_x, unsafe { tb_height() })
+                let (_, _, map_x, map_y) = DisplayWindow::Map
+                    .dimensions(width, height);
+                (0, map_y + 1, map_x, height)
             },
             DisplayWindow::Detail => {
-                let (_, _, map_x, _) = DisplayWindow::Map.dimensions();
-                (map_x + 1, 0, unsafe { tb_width() },
-                    unsafe { tb_height() })
+                let (_, _, map_x, _) = DisplayWindow::Map
+                    .dimensions(width, height);
+                (map_x + 1, 0, width, height)
             },
         }
     }
 }
 
-pub enum DisplayMode {
-    SDL, Console,
+// the tile at the top left of the map, which is centred on the player
+fn map_origin(st: &State, max_x: i32, max_y: i32) -> (i32, i32) {
+    let (cur_y, cur_x) = st.dungeon.player.coords;
+    (cur_y as i32 - (max_y / 2), cur_x as i32 - (max_x / 2))
 }
 
-pub struct Display<'a> {
-    mode: DisplayMode,
+pub struct Display<'a, R> {
+    renderer: R,
     materials: &'a HashMap<String, MaterialInfo>,
+    mobs: &'a HashMap<String, MobTemplate>,
+    glyphs: Glyphs,
+
+    // the keybindings and what they do, for the help screen
+    help: Vec<(String, String)>,
 }
 
-impl Display<'_> {
-    pub fn new<'a>(
-        mode: DisplayMode,
+impl<'a, R> Display<'a, R>
+where
+    R: Renderer
+{
+    pub fn new(
+        renderer: R,
         mats: &'a HashMap<String, MaterialInfo>,
-    ) -> Display<'a> {
-        match unsafe { tb_init() } {
-            TB_EFAILED_TO_OPEN_TTY => {
-                eprintln!("error: could not open terminal");
-                std::process::exit(1);
-            },
-            TB_EUNSUPPORTED_TERMINAL => {
-                eprintln!("error: unsupported terminal");
-                eprintln!("hint: try using another terminal (such as xterm)");
-                std::process::exit(1);
-            },
-            TB_EPIPE_TRAP_ERROR => {
-                eprintln!("error: could not initialize screen");
-                std::process::exit(1);
-            },
-            _ => (),
+        mobs: &'a HashMap<String, MobTemplate>,
+        glyphs: Glyphs,
+        help: Vec<(String, String)>,
+    ) -> Display<'a, R> {
+        Display {
+            renderer: renderer, materials: mats, mobs: mobs,
+            glyphs: glyphs, help: help,
         }
+    }
+
+    pub fn renderer(&self) -> &R {
+        &self.renderer
+    }
 
-        unsafe {
-            tb_select_output_mode(TB_OUTPUT_TRUECOLOR);
-            tb_set_clear_attributes(TB_WHITE, TB_BLACK);
-            tb_clear();
+    pub fn present(&mut self) {
+        self.renderer.present();
+    }
+
+    fn window(&self, window: DisplayWindow) -> (i32, i32, i32, i32) {
+        window.dimensions(self.renderer.width(), self.renderer.height())
+    }
+
+    // the tile drawn at a spot on the screen (e.g. where the player
+    // clicked), if the map's there
+    pub fn map_coords_at(&self, st: &State, x: i32, y: i32) -> Option<(usize, usize)> {
+        if st.help || st.scrollback.is_some() || st.menu.is_some() {
+            return None;
         }
 
-        Display { mode: mode, materials: mats }
+        let (startx, starty, max_x, max_y) = self.window(DisplayWindow::Map);
+        if x < startx || y < starty || x >= max_x || y >= max_y {
+            return None;
+        }
+
+        let (top, left) = map_origin(st, max_x, max_y);
+        let (map_y, map_x) = (top + y - starty, left + x - startx);
+
+        let level = &st.dungeon.levels[st.dungeon.player.level];
+        if map_y < 0 || map_x < 0 ||
+            map_y as usize >= level.height || map_x as usize >= level.width {
+                return None;
+        }
+
+        Some((map_y as usize, map_x as usize))
     }
 
-    pub fn present(&self) {
-        unsafe {
-            tb_present();
+    pub fn draw(&mut self, st: &State) {
+        if st.help {
+            self.draw_help();
+            return;
+        }
+
+        if let Some(offset) = st.scrollback {
+            self.draw_scrollback(st, offset);
+            return;
+        }
+
+        match st.menu {
+            Some(menu) => self.draw_menu(st, menu),
+            None => self.draw_map(st),
         }
+        self.draw_messages(st);
+        self.draw_detail(st);
     }
 
-    pub fn draw(&self, st: &State) {
-        match &self.mode {
-            DisplayMode::SDL => unimplemented!(),
-            DisplayMode::Console => self.draw_console(st),
+    // the game's over; say how it ended, and where the
+    // morgue file went
+    pub fn draw_death_screen(&mut self, st: &State, morgue: &Result<String, String>) {
+        let r = &mut self.renderer;
+        r.clear();
+
+        let (max_x, max_y) = (r.width(), r.height());
+        let mut yctr = r.put_string(max_x, max_y, 2, 1, "You die...",
+            0xff7777, 0x000000, false).0 + 1;
+
+        for line in summary(st) {
+            yctr = r.put_string(max_x, max_y, 2, yctr, &line,
+                0xffffff, 0x000000, false).0;
         }
+
+        let note = match morgue {
+            Ok(path) => format!("Morgue file written to {}.", path),
+            Err(e) => format!("Could not write morgue file: {}", e),
+        };
+        yctr = r.put_string(max_x, max_y, 2, yctr + 1, &note,
+            0xffffff, 0x000000, false).0;
+        r.put_string(max_x, max_y, 2, yctr + 1, "Press any key to exit.",
+            0xffffff, 0x000000, false);
     }
 
-    pub fn draw_console(&self, st: &State) {
-        self.draw_console_map(st);
-        self.draw_console_messages(st);
+    // draw information about the player to the side of the map
+    pub fn draw_detail(&mut self, st: &State) {
+        let (xctr, mut yctr, max_x, max_y) =
+            self.window(DisplayWindow::Detail);
+        let lvl = st.dungeon.player.level;
+        let level = &st.dungeon.levels[lvl];
+        let player_id = st.dungeon.player_mob_id();
+        let player_mob = &st.dungeon.mobs[&player_id];
+
+        let mobs = self.mobs;
+        let glyphs = self.glyphs;
+        let r = &mut self.renderer;
+        let mut put = |yctr: i32, text: &str, fg: u32| {
+            if yctr >= max_y { return yctr; }
+            r.put_string(max_x, max_y, xctr, yctr, text, fg, 0x000000, false).0
+        };
+
+        yctr = put(yctr, &st.dungeon.world_name, 0xffffff);
+        let depth = if level.name.is_empty() {
+            format!("level {}", lvl + 1)
+        } else {
+            format!("{}, level {}", level.name, lvl + 1)
+        };
+        yctr = put(yctr, &depth, 0xbbbbbb);
+        yctr = put(yctr, &format!("the {}", player_title(&st.dungeon)), 0xffffff);
+        yctr = put(yctr, "", 0xffffff);
+
+        // stats, coloured by how far below their max they are
+        for (name, max, percentage) in player_mob.stats() {
+            let current = (max as usize * percentage as usize) / 100;
+            let fg = match percentage {
+                75..=255 => 0xffffff,
+                40..=74 => 0xffff77,
+                _ => 0xff7777,
+            };
+            let line = format!("{:<12} {:>3} {:>3}%", name, current, percentage);
+            yctr = put(yctr, &line, fg);
+        }
+
+        let celsius = player_mob.normal_body_temperature as f64 - 273.15;
+        let degrees = match glyphs {
+            Glyphs::Unicode => "°C",
+            Glyphs::Ascii => "C",
+        };
+        yctr = put(yctr, &format!("{:<12} {:>3}%", "blood",
+            player_mob.blood_percentage()), 0xffffff);
+        yctr = put(yctr, &format!("{:<12} {:.1}{}", "temperature", celsius,
+            degrees), 0xffffff);
+        yctr = put(yctr, "", 0xffffff);
+
+        // list the mobs the player can see, how hurt they look,
+        // and whether they've noticed the player yet
+        for coord in &player_mob.fov {
+            let (y, x): (usize, usize) = coord.as_yx();
+            let mob_id = match level.d[y][x].mobs {
+                Some(id) if id != player_id => id,
+                _ => continue,
+            };
+
+            let mob = &st.dungeon.mobs[&mob_id];
+            let awareness = mob.awareness();
+            let fg = match awareness {
+                Awareness::Unaware => 0x77ff77,
+                Awareness::Suspicious => 0xffff77,
+                Awareness::Alerted => 0xff7777,
+            };
+            let line = format!("{} {}, {} ({})", glyphs.mob(mob),
+                mobs[&mob.from_mob_template].short_name,
+                apparent_wounds(mob), awareness.name());
+            yctr = put(yctr, &line, fg);
+        }
+
+        // clear whatever was left over from the last tick
+        while yctr < max_y {
+            yctr = put(yctr, "", 0xffffff);
+        }
     }
 
-    pub fn draw_console_messages(&self, st: &State) {
+    // draw the inventory (or a list of items to choose from)
+    // over the map
+    pub fn draw_menu(&mut self, st: &State, menu: Menu) {
         let (xctr, mut yctr, max_x, max_y) =
-            DisplayWindow::Message.dimensions();
-        let mut msgctr = 0;
-        let displayed_min = st.messages.len()
-            .saturating_sub(((max_y - yctr) - 1) as usize);
-        let messages = &st.messages[displayed_min..st.messages.len()];
+            self.window(DisplayWindow::Map);
+        let player = &st.dungeon.mobs[&st.dungeon.player_mob_id()];
+        let letter = |i: usize| (b'a' + i as u8) as char;
+        let kg = |grams: usize| format!("{:.1}kg", grams as f64 / 1000.0);
+
+        let title = match menu {
+            Menu::Inventory => "Inventory",
+            Menu::Drop => "Drop what?",
+            Menu::Equip => "Wear or wield what?",
+            Menu::Unequip => "Take off what?",
+            Menu::Eat => "Eat what?",
+            Menu::Drink => "Drink what?",
+        };
+
+        let mut lines = Vec::new();
+        match menu {
+            Menu::Unequip => {
+                let slots = player.equipment_slots().into_iter()
+                    .filter(|s| player.equipment.contains_key(s));
+                for (i, slot) in slots.enumerate() {
+                    lines.push(format!("{}) {} ({})", letter(i),
+                        player.equipment[&slot].name, slot.name()));
+                }
+            },
+            _ => {
+                for (i, item) in player.inventory.iter().enumerate() {
+                    lines.push(format!("{}) {} ({})", letter(i),
+                        item.name, kg(item.weight)));
+                }
 
-        while yctr < max_y && msgctr < messages.len() {
-            let message = &messages[msgctr];
+                if player.inventory.is_empty() {
+                    lines.push("(nothing)".to_string());
+                }
+            },
+        }
+
+        if menu == Menu::Inventory {
+            lines.push("".to_string());
+            lines.push("Equipment".to_string());
+            for slot in player.equipment_slots() {
+                let name = match player.equipment.get(&slot) {
+                    Some(item) => item.name.clone(),
+                    None => "-".to_string(),
+                };
+                lines.push(format!("  {:<10} {}", slot.name(), name));
+            }
+
+            lines.push("".to_string());
+            lines.push(format!("Carrying {} of {}",
+                kg(player.carried_weight()), kg(player.max_carried_weight())));
+        }
+
+        // clear the window first
+        let r = &mut self.renderer;
+        for y in yctr..max_y {
+            r.put_string(max_x, max_y, xctr, y, "", 0xffffff, 0x000000, false);
+        }
+
+        yctr = r.put_string(max_x, max_y, xctr, yctr, title,
+            0x000000, 0xffffff, false).0 + 1;
+        for line in &lines {
+            if yctr >= max_y { break; }
+            yctr = r.put_string(max_x, max_y, xctr, yctr, line,
+                0xffffff, 0x000000, false).0;
+        }
+    }
+
+    pub fn draw_messages(&mut self, st: &State) {
+        let (xctr, mut yctr, max_x, max_y) =
+            self.window(DisplayWindow::Message);
+
+        // in look mode, describe whatever's under the
+        // cursor instead
+        if let Some(Cursor { mode: CursorMode::Look, coords }) = st.cursor {
+            for line in describe(st, self.mobs, self.materials, coords) {
+                if yctr >= max_y { break; }
+                yctr = self.renderer.put_string(max_x, max_y, xctr, yctr, &line,
+                    0xffffff, 0x000000, true).0;
+            }
+
+            // clear whatever was there before
+            while yctr < max_y {
+                yctr = self.renderer.put_string(max_x, max_y, xctr, yctr, "",
+                    0xffffff, 0x000000, false).0;
+            }
+            return;
+        }
 
-            //let p = message.priority.as_usize();
-            //let tocol = |p, i| return clamp(p * i, 0, 255);
-            //let fg = Color::new(tocol(p, 65), tocol(p, 60),
-                //tocol(p, 60), 0).as_u32();
+        let count = ((max_y - yctr) - 1) as usize;
+        for message in st.dungeon.messages.recent(count) {
+            if yctr >= max_y { break; }
 
             let bg = Color::new(0, 0, 0, 0).as_u32();
 
             // why the f does termbox not support TB_BOLD with
             // true color?!
-            let res = tb_put_string(max_x, max_y, xctr, yctr,
-                &message.text, 0xffffff, bg, false);
-            yctr = res.0; msgctr += 1;
+            let res = self.renderer.put_string(max_x, max_y, xctr, yctr,
+                &message.full_text(), priority_color(message.priority), bg, false);
+            yctr = res.0;
+        }
+    }
+
+    // the whole message log, taking up the whole screen
+    pub fn draw_scrollback(&mut self, st: &State, offset: usize) {
+        let r = &mut self.renderer;
+        r.clear();
+
+        let (max_x, max_y) = (r.width(), r.height());
+        let mut yctr = r.put_string(max_x, max_y, 0, 0,
+            "Message log (scroll with the movement keys, ESC to close)",
+            0x000000, 0xffffff, false).0;
+
+        // the newest messages go at the bottom; scrolling
+        // back moves further up the log
+        let shown = (max_y - yctr) as usize;
+        let messages = st.dungeon.messages.all();
+        let end = messages.len().saturating_sub(offset);
+        let start = end.saturating_sub(shown);
+
+        for message in &messages[start..end] {
+            yctr = r.put_string(max_x, max_y, 0, yctr, &message.full_text(),
+                priority_color(message.priority), 0x000000, false).0;
+        }
+    }
+
+    pub fn draw_help(&mut self) {
+        let r = &mut self.renderer;
+        r.clear();
+
+        let (max_x, max_y) = (r.width(), r.height());
+        let mut yctr = r.put_string(max_x, max_y, 0, 0,
+            "Keybindings (press any key to close)",
+            0x000000, 0xffffff, false).0;
+
+        let width = self.help.iter()
+            .map(|(keys, _)| keys.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (keys, description) in &self.help {
+            let line = format!("{:<width$}  {}", keys, description, width = width);
+            yctr = r.put_string(max_x, max_y, 0, yctr, &line,
+                0xffffff, 0x000000, false).0;
         }
     }
 
-    pub fn draw_console_map(&self, st: &State) {
+    pub fn draw_map(&mut self, st: &State) {
         let level = &st.dungeon.levels[st.dungeon.player.level];
         let cur_y = st.dungeon.player.coords.0 as i32;
         let cur_x = st.dungeon.player.coords.1 as i32;
@@ -130,11 +405,10 @@ impl Display<'_> {
         // xctr/yctr is the current position on the screen
         // max_x/max_y is the maximum size of a window
         let (mut xctr, mut yctr, max_x, max_y) =
-            DisplayWindow::Map.dimensions();
+            self.window(DisplayWindow::Map);
 
-        let starty = cur_y - (max_y / 2);
+        let (starty, startx) = map_origin(st, max_x, max_y);
         let endy   = cur_y + (max_y / 2);
-        let startx = cur_x - (max_x / 2);
         let endx   = cur_x + (max_x / 2);
 
         for y in starty..endy {
@@ -144,9 +418,7 @@ impl Display<'_> {
                 // if out of bounds of the map, just draw a black tile
                 if (y < 0 || x < 0) ||
                     (y as usize >= level.height || x as usize >= level.width) {
-                        unsafe {
-                            tb_change_cell(xctr, yctr, ' ' as u32, 0xffffff, 0x000000);
-                        }
+                        self.renderer.put_cell(xctr, yctr, Cell::blank());
 
                         if xctr >= max_x { break; }
                         else { xctr += 1; }
@@ -167,7 +439,7 @@ impl Display<'_> {
 
                         cell.bg = Color::from(cell.bg).darken(5).as_u32();
                         cell.fg = Color::from(cell.fg).darken(5).as_u32();
-                        cell.ch = if tile.tiletype == TileType::Wall { ' ' as u32 }
+                        cell.ch = if tile.tiletype == TileType::Wall { ' ' }
                                   else { cell.ch };
                     } else {
                         // the player hasn't seen this tile at all;
@@ -182,10 +454,18 @@ impl Display<'_> {
                     cell.bg = Color::new(255, 255, 255, 0)
                         .as_u32();
                     cell.fg = Color::new(0, 0, 0, 0).as_u32();
-                    cell.ch = '@' as u32;
+                    cell.ch = '@';
+                }
+
+                // highlight the tile under the cursor
+                if let Some(cursor) = st.cursor {
+                    if (y as usize, x as usize) == cursor.coords {
+                        cell.bg = Color::new(255, 255, 0, 0).as_u32();
+                        cell.fg = Color::new(0, 0, 0, 0).as_u32();
+                    }
                 }
 
-                unsafe { tb_put_cell(xctr, yctr, &cell); }
+                self.renderer.put_cell(xctr, yctr, cell);
 
                 if xctr >= max_x { break; }
                 else { xctr += 1; }
@@ -198,9 +478,9 @@ impl Display<'_> {
         }
     }
 
-    // helper func to get a single tile as a RawCell
+    // helper func to get a single tile as a Cell
     fn tile_as_cell(&self, tile: &DungeonTile, mob_table: &HashMap<u64, Mob>)
-        -> RawCell
+        -> Cell
     {
         let tile_material = &self.materials[&tile.tile_material];
         let mut bg = tile_material.color_bg;
@@ -210,20 +490,25 @@ impl Display<'_> {
         match tile.tiletype {
             TileType::Debug
             | TileType::Wall  => {
-                glyph = tile_material.block_glyph;
+                glyph = self.glyphs.block(tile_material);
             },
             TileType::Floor => {
-                glyph = '·';
+                glyph = self.glyphs.floor();
                 bg = bg.darken(32);
             },
         }
 
-        if let Some(mob_id) = &tile.mobs {
-            assert!(mob_table.contains_key(mob_id));
-            let mob = &mob_table[mob_id];
+        if let Some(item) = tile.items.last() {
+            glyph = self.glyphs.item(item);
+            if let Some(item_material) = self.materials.get(&item.material) {
+                fg = item_material.color_fg;
+            }
+        }
 
+        // the player may remember a mob that has since died
+        if let Some(mob) = tile.mobs.and_then(|id| mob_table.get(&id)) {
             bg = Color::new(0, 0, 0, 0);
-            glyph = mob.unicode_glyph;
+            glyph = self.glyphs.mob(mob);
             if let Some(mob_fg) = mob.glyph_fg {
                 fg = mob_fg;
             } else {
@@ -231,16 +516,10 @@ impl Display<'_> {
             }
         }
 
-        RawCell {
-            ch: glyph as u32,
-            fg: fg.as_u32(),
-            bg: bg.as_u32(),
-        }
+        Cell::new(glyph, fg.as_u32(), bg.as_u32())
     }
 
-    pub fn close(&self) {
-        unsafe {
-            tb_shutdown();
-        }
+    pub fn close(&mut self) {
+        self.renderer.close();
     }
 }
@@ -0,0 +1,118 @@
+// driving the game, without a terminal
+//
+// a Game is everything the game needs to run (the state, the info
+// files, and the rng), and moves it along one input at a time. the
+// termbox frontend in main.rs is built on top of it, and so is
+// anything else that wants to play th without a screen, e.g. tests:
+//
+//     let mut game = Game::new(st, &mobs, &materials, seed);
+//     game.act(KeybindingAction::Move(Direction::North));
+//     game.wait(10);
+//     assert_eq!(game.dungeon().player.coords, (4, 5));
+
+use crate::input::*;
+use crate::kbd::*;
+use crate::replay::*;
+use crate::state::*;
+use crate::tick;
+use lib::dungeon::*;
+use lib::material::*;
+use lib::mob::*;
+use rand::prelude::*;
+use std::collections::HashMap;
+
+pub struct Game<'a> {
+    pub st: State,
+    pub mobs: &'a HashMap<String, MobTemplate>,
+    pub materials: &'a HashMap<String, MaterialInfo>,
+    rng: StdRng,
+}
+
+impl<'a> Game<'a> {
+    // to record the game, set st.replay before calling this, since
+    // the replay has to start from the state as it was loaded
+    pub fn new(st: State, mobs: &'a HashMap<String, MobTemplate>,
+        materials: &'a HashMap<String, MaterialInfo>, seed: u64) -> Game<'a>
+    {
+        let mut game = Game {
+            st: st,
+            mobs: mobs,
+            materials: materials,
+            rng: StdRng::seed_from_u64(seed),
+        };
+
+        // let the player see where they are
+        tick::player_tick(&mut game.st);
+        game
+    }
+
+    // the game a replay starts from, ready to play it back
+    pub fn from_replay(replay: &Replay, mobs: &'a HashMap<String, MobTemplate>,
+        materials: &'a HashMap<String, MaterialInfo>) -> Result<Game<'a>, String>
+    {
+        Ok(Game::new(replay.start_state()?, mobs, materials, replay.seed))
+    }
+
+    // handle an input, recording it if the game's being recorded
+    pub fn input(&mut self, input: Input) -> Outcome {
+        self.st.record_input(input);
+        let outcome = handle_input(&mut self.st, input, self.mobs,
+            self.materials, &mut self.rng);
+        self.st.record_checksum();
+        outcome
+    }
+
+    pub fn act(&mut self, action: KeybindingAction) -> Outcome {
+        self.input(Input::Action(action))
+    }
+
+    // let a number of turns go by
+    pub fn wait(&mut self, turns: u64) {
+        for _ in 0..turns {
+            if self.is_over() {
+                break;
+            }
+            self.act(KeybindingAction::Wait);
+        }
+    }
+
+    // take a step towards wherever the player's autotravelling to,
+    // returning false once they've stopped
+    pub fn travel_step(&mut self) -> bool {
+        if self.st.autotravel.is_none() {
+            return false;
+        }
+
+        self.input(Input::TravelStep);
+        self.st.autotravel.is_some()
+    }
+
+    // autotravel until the player gets there, or is interrupted
+    pub fn travel(&mut self) {
+        while self.travel_step() {}
+    }
+
+    // travel to a tile, as if the player had double-clicked on it
+    pub fn travel_to(&mut self, coords: (usize, usize)) {
+        self.input(Input::TravelTo(coords));
+        self.travel();
+    }
+
+    pub fn stop_travel(&mut self) {
+        if self.st.autotravel.is_some() {
+            self.input(Input::StopTravel);
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.st.ending.is_some()
+    }
+
+    pub fn dungeon(&self) -> &Dungeon {
+        &self.st.dungeon
+    }
+
+    pub fn player(&self) -> &Mob {
+        &self.st.dungeon.mobs[&self.st.dungeon.player_mob_id()]
+    }
+}
@@ -0,0 +1,151 @@
+// everything the player can do to the game, whether it comes
+// straight from the keyboard or from a replay
+//
+// keys are kept along with whatever action they were bound to when
+// they were pressed, so that a replay doesn't depend on the keybindings
+// of whoever's watching it.
+
+use crate::kbd::*;
+use crate::sched::*;
+use crate::state::*;
+use crate::tick;
+use crate::travel;
+use lib::keys::*;
+use lib::material::*;
+use lib::mob::*;
+use rand::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Key(KeyPress, Option<KeybindingAction>),
+
+    // an action that didn't come from a key (e.g. from a test),
+    // taken as if no menu or cursor were open
+    Action(KeybindingAction),
+
+    // take the next step towards wherever the
+    // player is autotravelling to
+    TravelStep,
+
+    // the player pressed a key to stop autotravelling
+    StopTravel,
+
+    // the player clicked on a tile, to look at it...
+    LookAt((usize, usize)),
+
+    // ...or double-clicked on it, to go there
+    TravelTo((usize, usize)),
+}
+
+// what's left for the caller to do after an input, since
+// the game itself never touches the save
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Continue,
+    Save,
+    Quit,
+}
+
+pub fn handle_input<R>(st: &mut State, input: Input,
+    mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, rng: &mut R) -> Outcome
+where
+    R: Rng
+{
+    match input {
+        Input::Key(key, action) => {
+            let outcome = handle_key(st, key, action, mobs, materials, rng);
+            tick::player_tick(st);
+            outcome
+        },
+        Input::Action(action) => {
+            let outcome = take_action(st, action, mobs, materials, rng);
+            tick::player_tick(st);
+            outcome
+        },
+        Input::TravelStep => {
+            travel_step(st, mobs, materials, rng);
+            Outcome::Continue
+        },
+        Input::StopTravel => {
+            st.autotravel = None;
+            Outcome::Continue
+        },
+        Input::LookAt(coords) => {
+            st.look_at(coords);
+            Outcome::Continue
+        },
+        Input::TravelTo(coords) => {
+            st.travel_to(coords);
+            Outcome::Continue
+        },
+    }
+}
+
+fn handle_key<R>(st: &mut State, key: KeyPress, action: Option<KeybindingAction>,
+    mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, rng: &mut R) -> Outcome
+where
+    R: Rng
+{
+    // while a menu is open, keypresses go to the menu,
+    // and likewise for the cursor and the message log.
+    // any key closes the help.
+    if st.help {
+        st.help = false;
+    } else if st.scrollback.is_some() {
+        st.handle_scrollback(key, action);
+    } else if st.cursor.is_some() {
+        st.handle_cursor(key, action);
+    } else if st.menu.is_some() {
+        if let Some(taken) = st.handle_menu(key, materials) {
+            tick::mobs_tick(st, taken, mobs, materials, rng);
+        }
+    } else if let Some(action) = action {
+        return take_action(st, action, mobs, materials, rng);
+    }
+
+    Outcome::Continue
+}
+
+fn take_action<R>(st: &mut State, action: KeybindingAction,
+    mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, rng: &mut R) -> Outcome
+where
+    R: Rng
+{
+    match action {
+        KeybindingAction::Save => Outcome::Save,
+        KeybindingAction::Quit => Outcome::Quit,
+        _ => {
+            if let Some(taken) = st.handle_action(action, mobs, materials, rng) {
+                tick::mobs_tick(st, taken, mobs, materials, rng);
+            }
+            Outcome::Continue
+        },
+    }
+}
+
+fn travel_step<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, rng: &mut R)
+where
+    R: Rng
+{
+    let d = match travel::next_step(st) {
+        Some(d) => d,
+        None => return,
+    };
+
+    let taken = st.handle_action(KeybindingAction::Move(d), mobs, materials, rng);
+    if let Some(taken) = taken {
+        tick::mobs_tick(st, taken, mobs, materials, rng);
+    }
+
+    // the player couldn't move (e.g. they've fainted)
+    if taken != Some(ActionKind::Move) || st.ending.is_some() {
+        st.autotravel = None;
+    }
+    tick::player_tick(st);
+}
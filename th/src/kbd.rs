@@ -1,105 +1,64 @@
 use lib::dirs::*;
-use crate::tb::*;
-use termbox_sys::*;
-use std::collections::HashMap;
+use lib::keys;
+use lib::keys::Action;
+use serde::{Serialize, Deserialize};
 
-#[derive(Copy, Clone)]
+// where the default keybindings are, if the
+// player hasn't set up their own
+const DEFAULT_KEYBINDINGS: &str = "../dat/keys/th.ron";
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum KeybindingAction {
     LevelUp,
     LevelDown,
     Move(Direction),
     Wait,
+    PickUp,
+    Drop,
+    Equip,
+    Unequip,
+    Inventory,
+    Eat,
+    Drink,
+    Sleep,
+    Explore,
+    Travel,
+    Look,
+    MessageLog,
+    Help,
     Save,
     Quit,
 }
 
-pub struct Keybinding {
-    trigger: EventType,
-    action: KeybindingAction,
+impl Action for KeybindingAction {
+    fn description(&self) -> String {
+        match self {
+            KeybindingAction::LevelUp => "go up a level".to_string(),
+            KeybindingAction::LevelDown => "go down a level".to_string(),
+            KeybindingAction::Move(d) => format!("move {}", d.name()),
+            KeybindingAction::Wait => "wait a turn".to_string(),
+            KeybindingAction::PickUp => "pick something up".to_string(),
+            KeybindingAction::Drop => "drop something".to_string(),
+            KeybindingAction::Equip => "wear or wield something".to_string(),
+            KeybindingAction::Unequip => "take something off".to_string(),
+            KeybindingAction::Inventory => "look at your inventory".to_string(),
+            KeybindingAction::Eat => "eat something".to_string(),
+            KeybindingAction::Drink => "drink something".to_string(),
+            KeybindingAction::Sleep => "go to sleep".to_string(),
+            KeybindingAction::Explore => "explore automatically".to_string(),
+            KeybindingAction::Travel => "travel somewhere".to_string(),
+            KeybindingAction::Look => "look around".to_string(),
+            KeybindingAction::MessageLog => "read old messages".to_string(),
+            KeybindingAction::Help => "show this help".to_string(),
+            KeybindingAction::Save => "save".to_string(),
+            KeybindingAction::Quit => "save and quit".to_string(),
+        }
+    }
 }
 
-pub struct Keybindings(Vec<Keybinding>);
-
-impl Keybindings {
-    // default keybindings
-    pub fn new() -> Keybindings {
-        let bindings = vec![
-            Keybinding {
-                trigger: EventType::Character('h'),
-                action: KeybindingAction::Move(Direction::West),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('j'),
-                action: KeybindingAction::Move(Direction::South),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('k'),
-                action: KeybindingAction::Move(Direction::North),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('l'),
-                action: KeybindingAction::Move(Direction::East),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('y'),
-                action: KeybindingAction::Move(Direction::NorthWest),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('u'),
-                action: KeybindingAction::Move(Direction::NorthEast),
-            },
-
-            Keybinding {
-                trigger: EventType::Character('b'),
-                action: KeybindingAction::Move(Direction::SouthWest),
-            },
+pub type Keybindings = keys::Keybindings<KeybindingAction>;
 
-            Keybinding {
-                trigger: EventType::Character('n'),
-                action: KeybindingAction::Move(Direction::SouthEast),
-            },
-
-            // wait
-            Keybinding {
-                trigger: EventType::Character('.'),
-                action: KeybindingAction::Wait,
-            },
-
-            // save
-            Keybinding {
-                trigger: EventType::Key(TB_KEY_CTRL_S),
-                action: KeybindingAction::Save,
-            },
-
-            // save and quit
-            Keybinding {
-                trigger: EventType::Key(TB_KEY_CTRL_C),
-                action: KeybindingAction::Quit,
-            },
-
-            Keybinding {
-                trigger: EventType::Key(TB_KEY_CTRL_Q),
-                action: KeybindingAction::Quit,
-            },
-        ];
-
-        Keybindings(bindings)
-    }
-
-    pub fn as_table(&self) -> HashMap<EventType, KeybindingAction> {
-        let mut table = HashMap::new();
-        for kbd in &self.0 {
-            if !table.contains_key(&kbd.trigger) {
-                table.entry(kbd.trigger).or_insert(kbd.action);
-            } else {
-                *table.get_mut(&kbd.trigger).unwrap() = kbd.action;
-            }
-        }
-        table
-    }
+// the player's own keybindings, or the default ones
+pub fn load_keybindings() -> Result<Keybindings, String> {
+    Keybindings::load("th", DEFAULT_KEYBINDINGS)
 }
@@ -0,0 +1,20 @@
+// the game itself, and drawing it to any Renderer (see lib::render)
+//
+// the th binary (main.rs) is a termbox frontend over this; see
+// game.rs for driving the game without one.
+
+pub mod autosave;
+pub mod combat;
+pub mod display;
+pub mod game;
+pub mod input;
+pub mod kbd;
+pub mod look;
+pub mod morgue;
+pub mod replay;
+pub mod sched;
+pub mod state;
+pub mod stealth;
+pub mod tick;
+pub mod titles;
+pub mod travel;
@@ -0,0 +1,102 @@
+// describing what's on a tile, for look mode
+//
+// only what the player can see right now is described as it
+// really is; anything else is described as the player remembers it.
+
+use crate::state::*;
+use lib::coord::*;
+use lib::dun_s1::*;
+use lib::dun_s2::*;
+use lib::material::*;
+use lib::mob::*;
+use std::collections::HashMap;
+
+// how badly hurt a mob looks, going by how much blood
+// it's lost
+pub fn apparent_wounds(mob: &Mob) -> &'static str {
+    match mob.blood_percentage() {
+        90..=100 => "unhurt",
+        70..=89 => "lightly wounded",
+        45..=69 => "wounded",
+        20..=44 => "badly wounded",
+        _ => "almost dead",
+    }
+}
+
+// what a mob looks like it's doing
+fn apparent_mode(mob: &Mob) -> &'static str {
+    match mob.current_mode {
+        MobMode::AttackMob => "fighting",
+        MobMode::Eat => "looking for food",
+        MobMode::Drink => "looking for water",
+        MobMode::Sleep => "asleep",
+        MobMode::Unconscious => "unconscious",
+        MobMode::CompleteJob | MobMode::FindJob => "busy",
+        MobMode::Wander => "wandering around",
+    }
+}
+
+fn describe_tile(tile: &DungeonTile,
+    materials: &HashMap<String, MaterialInfo>) -> Vec<String>
+{
+    let mut lines = Vec::new();
+
+    match materials.get(&tile.tile_material) {
+        Some(mat) => {
+            let what = match tile.tiletype {
+                TileType::Wall | TileType::Debug => "wall",
+                TileType::Floor => "floor",
+            };
+            lines.push(format!("A {} {}. {}", mat.name, what, mat.description));
+        },
+        None => lines.push(format!("Some {}.", tile.tile_material)),
+    }
+
+    for item in &tile.items {
+        lines.push(format!("There is a {} here.", item.name));
+    }
+
+    lines
+}
+
+// describe the tile at coords on the player's level
+pub fn describe(st: &State, mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, coords: (usize, usize)) -> Vec<String>
+{
+    let level = &st.dungeon.levels[st.dungeon.player.level];
+    let player_id = st.dungeon.player_mob_id();
+    let player = &st.dungeon.mobs[&player_id];
+    let coord = Coord::from(coords);
+
+    if !player.fov.contains(&coord) {
+        return match player.memory.get(&coord) {
+            Some(tile) => {
+                let mut lines = vec!["You remember...".to_string()];
+                lines.append(&mut describe_tile(tile, materials));
+                lines
+            },
+            None => vec!["You don't know what's there.".to_string()],
+        };
+    }
+
+    let tile = &level.d[coords.0][coords.1];
+    let mut lines = Vec::new();
+
+    match tile.mobs {
+        Some(id) if id == player_id => lines.push("That's you.".to_string()),
+        Some(id) => {
+            let mob = &st.dungeon.mobs[&id];
+            let template = &mobs[&mob.from_mob_template];
+            lines.push(format!("A {}, {} and {}.", template.long_name,
+                apparent_wounds(mob), apparent_mode(mob)));
+            if mob.bleeding > 0 {
+                lines.push("It is bleeding.".to_string());
+            }
+            lines.push(template.description.clone());
+        },
+        None => (),
+    }
+
+    lines.append(&mut describe_tile(tile, materials));
+    lines
+}
@@ -1,39 +1,72 @@
-mod display;
-mod kbd;
-mod message;
-mod state;
-mod tb;
-mod tick;
-
-use crate::display::*;
-use crate::kbd::*;
-use crate::state::*;
-use crate::tb::*;
+use th::autosave;
+use th::display::*;
+use th::game::*;
+use th::input::*;
+use th::kbd::*;
+use th::morgue;
+use th::replay::{self, *};
+use th::state::*;
 use lib::info_files::*;
+use lib::keys::*;
+use lib::message::*;
 use lib::mob::*;
 use lib::material::*;
+use lib::priority::*;
+use lib::render::*;
+use lib::termbox::*;
 use termbox_sys::*;
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+// how many milliseconds a replay waits between inputs by
+// default, and at most
+const REPLAY_DELAY: i32 = 100;
+const MAX_REPLAY_DELAY: i32 = 5000;
+
+// the size of the screen that --screen draws the end of a replay on
+const DUMP_WIDTH: i32 = 100;
+const DUMP_HEIGHT: i32 = 40;
+
+// how soon a second click on the same tile has to come
+// after the first for the two to be a double-click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+// how --screen prints the screen out
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ScreenDump {
+    Plain,
+    Ansi,
+}
 
 fn main() {
     // set a custom panic handler that calls tb_shutdown
     // before printing anything
+    //
+    // the hook can't get at the game, so the emergency save
+    // is made once the panic has unwound back into main()
     std::panic::set_hook(Box::new(|panic_info| {
         unsafe { tb_shutdown(); }
         println!("aborting due to fatal error (see below):");
         println!("{}", panic_info);
         println!("stack backtrace:");
         println!("{:?}", backtrace::Backtrace::new());
-        println!("please report this issue upstream at {}.",
-            "https://github.com/kiedtl/th");
+        println!("please report this issue upstream at https://github.com/kiedtl/th.");
     }));
 
     // check arguments
-    let args = std::env::args().collect::<Vec<String>>();
+    let mut args = std::env::args().collect::<Vec<String>>();
+    let options = match DisplayOptions::from_args(&mut args) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            usage(&args[0]);
+        },
+    };
+
     if args.len() < 2 {
         eprintln!("{}: need dungeon file.", args[0]);
-        eprintln!("usage: {} <file>", args[0]);
-        std::process::exit(1);
+        usage(&args[0]);
     }
 
     let materials: HashMap<String, MaterialInfo> =
@@ -42,8 +75,14 @@ fn main() {
     let mobs: HashMap<String, MobTemplate> =
         load_info_files("../dat/mobs/").unwrap();
 
+    if args[1] == "--replay" {
+        replay_main(&args, options, &materials, &mobs);
+        return;
+    }
+
     // try to load map
-    let mut st = match State::from_file(&args[1]) {
+    let restore = autosave::offer_restore(&args[1]);
+    let mut st = match State::from_file(&args[1], restore) {
         Ok(s) => s,
         Err(e) => {
             println!("{}: \"{}\": {}", args[0], args[1], e);
@@ -51,71 +90,352 @@ fn main() {
         },
     };
 
-    tick::player_tick(&mut st);
+    // the rng is seeded here, rather than left to itself, so
+    // that the game can be recorded and replayed exactly
+    let seed: u64 = rand::random();
+    st.replay = match Replay::new(&st, seed) {
+        Ok(r) => Some(r),
+        Err(e) => {
+            eprintln!("{}: warning: not recording this game: {}", args[0], e);
+            None
+        },
+    };
+
+    let mut game = Game::new(st, &mobs, &materials, seed);
 
     // keybindings
-    let kbd = Keybindings::new();
+    let kbd = match load_keybindings() {
+        Ok(k) => k,
+        Err(e) => {
+            println!("{}: failed to load keybindings: {}", args[0], e);
+            std::process::exit(1);
+        },
+    };
     let keybinds = kbd.as_table();
 
-    let mut rng = rand::thread_rng();
-
     // termbox display
-    let display = Display::new(DisplayMode::Console, &materials);
+    let mut display = Display::new(Termbox::init(options.palette),
+        &materials, &mobs, options.glyphs, kbd.help());
 
-    display.draw(&st);
+    display.draw(&game.st);
     display.present();
 
-    // main loop
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        play(&mut game, &mut display, &keybinds)
+    }));
+
+    match result {
+        Ok(Ok(())) => display.close(),
+        Ok(Err(e)) => {
+            display.close();
+            eprintln!("error: {}", e);
+            emergency_save(&game.st);
+            std::process::exit(1);
+        },
+
+        // the panic hook has already shut termbox down
+        // and said what went wrong
+        Err(_) => {
+            emergency_save(&game.st);
+            std::process::exit(101);
+        },
+    }
+}
+
+// telling clicks from double-clicks
+struct Clicks {
+    last: Option<(Instant, (usize, usize))>,
+}
+
+impl Clicks {
+    fn new() -> Clicks {
+        Clicks { last: None }
+    }
+
+    // what a click on a tile does: the first looks at it,
+    // and a second one soon after goes there
+    fn click(&mut self, coords: (usize, usize)) -> Input {
+        let now = Instant::now();
+        match self.last {
+            Some((time, last)) if last == coords
+                && now.duration_since(time) < DOUBLE_CLICK_TIME => {
+                    self.last = None;
+                    Input::TravelTo(coords)
+            },
+            _ => {
+                self.last = Some((now, coords));
+                Input::LookAt(coords)
+            },
+        }
+    }
+}
+
+fn usage(arg0: &str) -> ! {
+    eprintln!("usage: {} [--ascii] [--colors <true|256|16|8>] <file>", arg0);
+    eprintln!("       {} [--ascii] [--colors <true|256|16|8>]", arg0);
+    eprintln!("                  --replay <file.replay> [--headless] [--delay <ms>]");
+    eprintln!("                  [--screen <plain|ansi>]");
+    std::process::exit(1);
+}
+
+fn emergency_save(st: &State) {
+    match st.emergency_save() {
+        Ok(path) => eprintln!("the game was saved to {}.", path),
+        Err(e) => eprintln!("the game couldn't be saved: {}", e),
+    }
+
+    if st.replay.is_some() {
+        match st.write_replay() {
+            Ok(()) => eprintln!("a replay of the game was saved to {}.", st.replay_path()),
+            Err(e) => eprintln!("the replay couldn't be saved: {}", e),
+        }
+    }
+}
+
+// the main loop, which returns when the player quits or dies
+fn play(game: &mut Game, display: &mut Display<Termbox>,
+    keybinds: &HashMap<KeyPress, KeybindingAction>) -> Result<(), String>
+{
+    let mut clicks = Clicks::new();
+
     loop {
         let mut raw_ev = RawEvent::new();
         let t = unsafe { tb_poll_event(&mut raw_ev) };
 
         if t == -1 {
-            display.close();
-            eprintln!("error: fatal termbox error");
-            // TODO: save
-            std::process::exit(1);
+            return Err("fatal termbox error".to_string());
         }
 
-        if t == (TB_EVENT_KEY as i32) {
-            let ev = EventType::from_rawevent(&raw_ev)
-                .unwrap();
-            match ev {
-                EventType::Character(_)
-                | EventType::Key(_) => {
-                    if !keybinds.contains_key(&ev) {
-                        continue;
-                    }
-
-                    let action = keybinds[&ev];
-                    match action {
-                        KeybindingAction::Save => {
-                            st.save_to_file().unwrap();
-                        },
-                        KeybindingAction::Quit => {
-                            st.save_to_file().unwrap();
-                            break; // close display and exit
-                        },
-                        _ => st.handle_action(action),
-                    }
-                },
-
-                // if the event type is EventType::Resize,
-                // then just ignore it as the screen will be
-                // redrawn later anyway
-                _ => (),
+        if t == (TB_EVENT_KEY as i32) || t == (TB_EVENT_MOUSE as i32) {
+            // clicking on the map looks at a tile, and double-clicking
+            // travels there. any other button (or wheel) does nothing,
+            // and nor do unknown keys.
+            let input = match EventType::from_rawevent(&raw_ev) {
+                Ok(EventType::Key(key)) =>
+                    Some(Input::Key(key, keybinds.get(&key).copied())),
+                Ok(EventType::Mouse(MouseButton::Left, x, y)) =>
+                    display.map_coords_at(&game.st, x, y)
+                        .map(|coords| clicks.click(coords)),
+                _ => None,
+            };
+
+            if let Some(input) = input {
+                match game.input(input) {
+                    Outcome::Save => {
+                        game.st.save_to_file().unwrap();
+                        let _ = game.st.write_replay();
+                    },
+                    Outcome::Quit => {
+                        game.st.save_to_file().unwrap();
+                        let _ = game.st.write_replay();
+                        return Ok(());
+                    },
+                    Outcome::Continue => (),
+                }
+            }
+
+            // keep walking until the player gets where they're going,
+            // or something (including a keypress) interrupts them
+            while game.travel_step() {
+                display.draw(&game.st);
+                display.present();
+
+                // letting go of the mouse button after double-clicking
+                // shouldn't stop the player as soon as they set off
+                let mut raw_ev = RawEvent::new();
+                let t = unsafe { tb_peek_event(&mut raw_ev, 0) };
+                let interrupted = match EventType::from_rawevent(&raw_ev) {
+                    _ if t <= 0 => false,
+                    Ok(EventType::Mouse(MouseButton::Release, _, _)) => false,
+                    Ok(EventType::Resize(_, _)) => false,
+                    _ => true,
+                };
+                if interrupted {
+                    game.stop_travel();
+                }
             }
 
-            tick::mobs_tick(&mut st, &mobs, &mut rng);
-            tick::player_tick(&mut st);
-            display.draw(&st);
+            display.draw(&game.st);
             display.present();
+
+            // the game's over
+            if game.is_over() {
+                game.st.delete_save();
+                let _ = game.st.write_replay();
+                let morgue = morgue::write_morgue(&game.st)
+                    .map_err(|e| e.to_string());
+                display.draw_death_screen(&game.st, &morgue);
+                display.present();
+
+                let mut raw_ev = RawEvent::new();
+                unsafe { tb_poll_event(&mut raw_ev); }
+                return Ok(());
+            }
+
+            game.st.maybe_autosave();
         } else if t == (TB_EVENT_RESIZE as i32) {
-            display.draw(&st);
+            display.draw(&game.st);
             display.present();
         }
     }
+}
+
+// play a replay back, either on screen or (with --headless)
+// just checking that it still plays out the way it did, and
+// (with --screen) printing out what the screen looked like at
+// the end
+fn replay_main(args: &[String], options: DisplayOptions,
+    materials: &HashMap<String, MaterialInfo>,
+    mobs: &HashMap<String, MobTemplate>)
+{
+    let mut path = None;
+    let mut headless = false;
+    let mut delay = REPLAY_DELAY;
+    let mut screen = None;
+
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--delay" => {
+                delay = match rest.next().and_then(|d| d.parse().ok()) {
+                    Some(d) => d,
+                    None => usage(&args[0]),
+                };
+            },
+            "--screen" => {
+                screen = match rest.next().map(|s| s.as_str()) {
+                    Some("plain") => Some(ScreenDump::Plain),
+                    Some("ansi") => Some(ScreenDump::Ansi),
+                    _ => usage(&args[0]),
+                };
+                headless = true;
+            },
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    let path = match path {
+        Some(p) => p,
+        None => usage(&args[0]),
+    };
+
+    let replay = match Replay::load(&path) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("{}: \"{}\": {}", args[0], path, e);
+            std::process::exit(1);
+        },
+    };
+
+    if replay.game_version != env!("CARGO_PKG_VERSION") {
+        eprintln!("{}: warning: the replay was recorded by version {}",
+            args[0], replay.game_version);
+    }
+
+    let mut game = match Game::from_replay(&replay, mobs, materials) {
+        Ok(g) => g,
+        Err(e) => {
+            println!("{}: \"{}\": {}", args[0], path, e);
+            std::process::exit(1);
+        },
+    };
+
+    if !replay::start_in_sync(&replay, game.dungeon()) {
+        eprintln!("{}: warning: the replay doesn't start where it was recorded",
+            args[0]);
+    }
+
+    let mut playback = Playback::new(&replay);
+
+    if headless {
+        check_replay(&mut game, &mut playback, options, screen);
+    } else {
+        watch_replay(&mut game, &mut playback, options, delay);
+    }
+}
+
+fn check_replay(game: &mut Game, playback: &mut Playback,
+    options: DisplayOptions, screen: Option<ScreenDump>)
+{
+    while !playback.is_done() {
+        if !playback.step(game) {
+            eprintln!("desync after input {}", playback.handled());
+        }
+    }
+
+    if let Some(dump) = screen {
+        let mut display = Display::new(CellBuffer::new(DUMP_WIDTH, DUMP_HEIGHT),
+            game.materials, game.mobs, options.glyphs, Vec::new());
+        display.draw(&game.st);
+
+        match dump {
+            ScreenDump::Plain => print!("{}", display.renderer().as_plain()),
+            ScreenDump::Ansi => print!("{}",
+                display.renderer().as_ansi(options.palette)),
+        }
+    }
+
+    println!("replayed {} inputs, up to turn {}; {} desyncs; final checksum {:016x}",
+        playback.len(), game.dungeon().turn(), playback.desyncs.len(),
+        game.dungeon().checksum());
+
+    if !playback.desyncs.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+// watch a replay, one input every delay milliseconds. + and - change
+// the speed, space pauses, and q or escape stop watching.
+fn watch_replay(game: &mut Game, playback: &mut Playback,
+    options: DisplayOptions, mut delay: i32)
+{
+    let help = load_keybindings().map(|k| k.help()).unwrap_or_default();
+    let mut display = Display::new(Termbox::init(options.palette),
+        game.materials, game.mobs, options.glyphs, help);
+    let mut paused = false;
+
+    loop {
+        if !paused && !playback.is_done() {
+            if !playback.step(game) {
+                game.st.dungeon.messages.push(Message::new(
+                    &format!("The replay has gone differently after input {}.",
+                        playback.handled()),
+                    Priority::Max));
+            }
+
+            if playback.is_done() {
+                game.st.dungeon.messages.push(Message::new(
+                    "The replay is over.", Priority::Max));
+            }
+        }
+
+        display.draw(&game.st);
+        display.present();
+
+        let mut raw_ev = RawEvent::new();
+        let t = if paused || playback.is_done() {
+            unsafe { tb_poll_event(&mut raw_ev) }
+        } else {
+            unsafe { tb_peek_event(&mut raw_ev, delay) }
+        };
+
+        if t == -1 {
+            display.close();
+            eprintln!("error: fatal termbox error");
+            std::process::exit(1);
+        }
+
+        if let Ok(EventType::Key(key)) = EventType::from_rawevent(&raw_ev) {
+            match key.key {
+                Key::Char('+') => delay /= 2,
+                Key::Char('-') => delay = (delay * 2).clamp(1, MAX_REPLAY_DELAY),
+                Key::Space => paused = !paused,
+                Key::Char('q') | Key::Escape => break,
+                _ => (),
+            }
+        }
+    }
 
-    #[allow(unreachable_code)]
     display.close();
 }
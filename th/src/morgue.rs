@@ -0,0 +1,127 @@
+// the summary of a finished game, shown on the death screen
+// and written out to a plain-text "morgue" file
+
+use crate::state::*;
+use crate::titles::*;
+use lib::coord::*;
+use lib::dun_s1::*;
+use lib::render::*;
+use std::error::Error;
+use std::fs;
+
+// how many of the last messages go in the morgue file
+const MORGUE_MESSAGES: usize = 20;
+
+// how many tiles around the player the snapshot
+// of the level shows in each direction
+const SNAPSHOT_RADIUS: usize = 12;
+
+// a few lines on how the game ended
+pub fn summary(st: &State) -> Vec<String> {
+    let record = &st.dungeon.player.stealth;
+    let cause = st.ending.as_deref().unwrap_or("still alive");
+
+    vec![
+        format!("the {}", player_title(&st.dungeon)),
+        format!("{} on level {}", cause, st.dungeon.player.level + 1),
+        format!("after {} turns", st.dungeon.turn()),
+        format!("killed {} mobs ({} unseen)", record.kills, record.unseen_kills),
+    ]
+}
+
+// write the morgue file next to the map, returning its path
+pub fn write_morgue(st: &State) -> Result<String, Box<dyn Error>> {
+    let path = format!("{}.morgue.txt", st.map_path);
+    let player = &st.dungeon.mobs[&st.dungeon.player_mob_id()];
+    let mut lines = summary(st);
+
+    lines.push(String::new());
+    lines.push("stats:".to_string());
+    for (name, max, percentage) in player.stats() {
+        let current = (max as usize * percentage as usize) / 100;
+        lines.push(format!("  {:<12} {:>3}/{:<3} ({}%)", name, current,
+            max, percentage));
+    }
+    lines.push(format!("  {:<12} {}%", "blood", player.blood_percentage()));
+
+    lines.push(String::new());
+    lines.push("equipment:".to_string());
+    for slot in player.equipment_slots() {
+        if let Some(item) = player.equipment.get(&slot) {
+            lines.push(format!("  {:<12} {}", slot.name(), item.name));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("inventory:".to_string());
+    for item in &player.inventory {
+        lines.push(format!("  {}", item.name));
+    }
+
+    lines.push(String::new());
+    lines.push("last messages:".to_string());
+    for message in st.dungeon.messages.recent(MORGUE_MESSAGES) {
+        lines.push(format!("  {}", message.full_text()));
+    }
+
+    lines.push(String::new());
+    lines.push("surroundings:".to_string());
+    lines.append(&mut snapshot(st));
+
+    fs::write(&path, lines.join("\n") + "\n")?;
+    Ok(path)
+}
+
+// an ascii picture of the level around the player, as
+// the player saw (or remembered) it
+fn snapshot(st: &State) -> Vec<String> {
+    let level = &st.dungeon.levels[st.dungeon.player.level];
+    let (py, px) = st.dungeon.player.coords;
+    let player = &st.dungeon.mobs[&st.dungeon.player_mob_id()];
+
+    let starty = py.saturating_sub(SNAPSHOT_RADIUS);
+    let endy = std::cmp::min(py + SNAPSHOT_RADIUS + 1, level.height);
+    let startx = px.saturating_sub(SNAPSHOT_RADIUS);
+    let endx = std::cmp::min(px + SNAPSHOT_RADIUS + 1, level.width);
+
+    let mut lines = Vec::new();
+    for y in starty..endy {
+        let mut line = String::from("  ");
+        for x in startx..endx {
+            let coord = Coord::from((y, x));
+            let seen = player.fov.contains(&coord);
+            let tile = if seen {
+                &level.d[y][x]
+            } else {
+                match player.memory.get(&coord) {
+                    Some(t) => t,
+                    None => {
+                        line.push(' ');
+                        continue;
+                    },
+                }
+            };
+
+            // only show mobs that the player can still see
+            let mob = tile.mobs.filter(|_| seen)
+                .and_then(|id| st.dungeon.mobs.get(&id));
+
+            let ch = if (y, x) == (py, px) {
+                '@'
+            } else if let Some(mob) = mob {
+                Glyphs::Ascii.mob(mob)
+            } else if let Some(item) = tile.items.last() {
+                Glyphs::Ascii.item(item)
+            } else {
+                match tile.tiletype {
+                    TileType::Wall | TileType::Debug => '#',
+                    TileType::Floor => '.',
+                }
+            };
+            line.push(ch);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines
+}
@@ -0,0 +1,183 @@
+// recording games so that they can be played back exactly, e.g. to
+// reproduce a bug
+//
+// a replay holds the game as it was when recording started, the seed
+// for the random number generator, and every input the player gave.
+// every so often it also holds a checksum of the dungeon, so that
+// playing it back can tell when the game has gone differently (say,
+// because the code's changed since it was recorded).
+
+use crate::game::*;
+use crate::input::*;
+use crate::state::*;
+use lib::dungeon::*;
+use lib::encoding::*;
+use lib::save::*;
+use lib::utils;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+
+// how many inputs go by between checksums
+const CHECKSUM_INTERVAL: usize = 20;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub game_version: String,
+
+    // the map the game was being played on
+    pub map_path: String,
+
+    pub seed: u64,
+
+    // the game when recording started, as a save, and a
+    // hash of it to make sure it hasn't been mangled
+    pub start: Vec<u8>,
+    pub start_hash: u64,
+
+    pub inputs: Vec<Input>,
+
+    // how many inputs had been handled, and the
+    // dungeon's checksum after them
+    pub checksums: Vec<(usize, u64)>,
+}
+
+pub fn replay_path_for(map_path: &str) -> String {
+    format!("{}.replay", map_path)
+}
+
+impl Replay {
+    // start recording a game, which should have just been loaded
+    pub fn new(st: &State, seed: u64) -> Result<Replay, String> {
+        let start = SaveFile::dungeon_to_bytes(&st.dungeon,
+            env!("CARGO_PKG_VERSION"), Encoding::Compressed)?;
+
+        Ok(Replay {
+            game_version: env!("CARGO_PKG_VERSION").to_string(),
+            map_path: st.map_path.clone(),
+            seed: seed,
+            start_hash: utils::calculate_hash(&start),
+            start: start,
+            inputs: Vec::new(),
+            checksums: vec![(0, st.dungeon.checksum())],
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Replay, Box<dyn Error>> {
+        let replay: Replay = decode(&fs::read(path)?)?;
+        if utils::calculate_hash(&replay.start) != replay.start_hash {
+            return Err("the game the replay starts from is corrupt".into());
+        }
+        Ok(replay)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        write_atomically(path, &encode(self, Encoding::Compressed)?)?;
+        Ok(())
+    }
+
+    // the game as it was when recording started
+    pub fn start_state(&self) -> Result<State, String> {
+        let dungeon = SaveFile::from_bytes(&self.start)?.dungeon;
+        Ok(State::new(dungeon, &self.map_path))
+    }
+}
+
+impl State {
+    // an input is recorded before it's handled, so that if it
+    // crashes the game, it's in the replay
+    pub fn record_input(&mut self, input: Input) {
+        if let Some(replay) = &mut self.replay {
+            replay.inputs.push(input);
+        }
+    }
+
+    // ...and the checksum afterwards
+    pub fn record_checksum(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            let handled = replay.inputs.len();
+            if handled % CHECKSUM_INTERVAL == 0 {
+                replay.checksums.push((handled, self.dungeon.checksum()));
+            }
+        }
+    }
+
+    pub fn replay_path(&self) -> String {
+        replay_path_for(&self.map_path)
+    }
+
+    pub fn write_replay(&self) -> Result<(), Box<dyn Error>> {
+        match &self.replay {
+            Some(replay) => replay.write(&self.replay_path()),
+            None => Ok(()),
+        }
+    }
+}
+
+// playing a replay back, one input at a time
+pub struct Playback {
+    inputs: Vec<Input>,
+    checksums: HashMap<usize, u64>,
+    handled: usize,
+
+    // the inputs after which the game had gone differently
+    pub desyncs: Vec<usize>,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Playback {
+        Playback {
+            inputs: replay.inputs.clone(),
+            checksums: replay.checksums.iter().copied().collect(),
+            handled: 0,
+            desyncs: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub fn handled(&self) -> usize {
+        self.handled
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.handled >= self.inputs.len()
+    }
+
+    // handle the next input, returning false if the game's no
+    // longer going the way it did when it was recorded. saving and
+    // quitting are left alone, since a replay shouldn't touch any
+    // files.
+    pub fn step(&mut self, game: &mut Game) -> bool {
+        if self.is_done() {
+            return true;
+        }
+
+        game.input(self.inputs[self.handled]);
+        self.handled += 1;
+
+        match self.checksums.get(&self.handled) {
+            Some(checksum) if *checksum != game.dungeon().checksum() => {
+                self.desyncs.push(self.handled);
+                false
+            },
+            _ => true,
+        }
+    }
+}
+
+// the checksum the game started with, to check
+// before anything's been replayed
+pub fn start_in_sync(replay: &Replay, dungeon: &Dungeon) -> bool {
+    replay.checksums.iter()
+        .find(|(handled, _)| *handled == 0)
+        .map(|(_, checksum)| *checksum == dungeon.checksum())
+        .unwrap_or(true)
+}
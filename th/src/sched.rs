@@ -0,0 +1,110 @@
+// a priority-queue based turn scheduler
+//
+// every action a mob takes costs a certain amount of game time,
+// depending on what the action is and how agile the mob is.
+// the mob with the earliest time in the queue gets to act next.
+
+use lib::dungeon::*;
+use lib::math::*;
+use lib::mob::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// how much game time a single one-turn action takes
+// for a mob with NORMAL_AGILITY
+pub const TURN: u64 = TIME_PER_TURN;
+pub const NORMAL_AGILITY: u64 = 30;
+
+// how many levels above and below the player are
+// simulated in full detail
+const ACTIVE_LEVEL_RANGE: usize = 0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ActionKind {
+    Move,
+    Wait,
+    PickUp,
+    Drop,
+    Equip,
+    Eat,
+    Drink,
+    Sleep,
+    Cast,
+    Attack,
+}
+
+impl ActionKind {
+    // how many turns the action takes to complete
+    // multi-turn actions (digging, eating, et cetera)
+    // just return more than one.
+    fn turns(&self) -> u64 {
+        match self {
+            ActionKind::Move => 1,
+            ActionKind::Wait => 1,
+            ActionKind::PickUp => 1,
+            ActionKind::Drop => 1,
+            ActionKind::Equip => 2,
+            ActionKind::Eat => 3,
+            ActionKind::Drink => 1,
+            ActionKind::Sleep => 50,
+            ActionKind::Cast => 2,
+            ActionKind::Attack => 1,
+        }
+    }
+}
+
+// how long it takes a mob to perform an action
+pub fn action_cost(mob: &Mob, action: ActionKind) -> u64 {
+    let agility = std::cmp::max(mob.current_agility() as u64, 1);
+    let delay = clamp(TURN * NORMAL_AGILITY / agility, TURN / 10, TURN * 10);
+    delay * action.turns()
+}
+
+// the levels which should be simulated in full detail
+pub fn active_levels(dungeon: &Dungeon) -> Vec<usize> {
+    let player_level = dungeon.player.level;
+    let start = player_level.saturating_sub(ACTIVE_LEVEL_RANGE);
+    let end = std::cmp::min(player_level + ACTIVE_LEVEL_RANGE + 1,
+        dungeon.levels.len());
+    (start..end).collect()
+}
+
+pub struct Scheduler {
+    pub time: u64,
+
+    // the levels whose mobs are currently in the queue
+    pub active_levels: Vec<usize>,
+
+    // (time of next action, mob id)
+    queue: BinaryHeap<Reverse<(u64, u64)>>,
+}
+
+impl Scheduler {
+    // no mobs are scheduled until their level is activated
+    pub fn from_dungeon(dungeon: &Dungeon) -> Scheduler {
+        Scheduler {
+            time: dungeon.time,
+            active_levels: Vec::new(),
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    // let a mob act again after some amount of time has passed
+    pub fn schedule(&mut self, mob_id: u64, delay: u64) {
+        self.queue.push(Reverse((self.time + delay, mob_id)));
+    }
+
+    // remove mobs from the queue
+    pub fn unschedule(&mut self, mob_ids: &[u64]) {
+        self.queue = self.queue.drain()
+            .filter(|Reverse((_, id))| !mob_ids.contains(id))
+            .collect();
+    }
+
+    // get the next mob to act, advancing the current time
+    pub fn pop_next(&mut self) -> Option<u64> {
+        let Reverse((time, mob_id)) = self.queue.pop()?;
+        self.time = time;
+        Some(mob_id)
+    }
+}
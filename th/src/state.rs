@@ -1,49 +1,197 @@
 use lib::coord::*;
+use lib::dirs::*;
+use crate::autosave::*;
+use crate::combat::*;
 use crate::kbd::*;
-use crate::message::*;
+use lib::keys::*;
+use lib::message::*;
+use crate::sched::*;
+use crate::replay::*;
+use crate::tick::*;
+use crate::travel::*;
 use lib::priority::*;
 use lib::dun_s1::*;
 use lib::dungeon::Dungeon;
+use lib::material::*;
+use lib::mob::*;
 use std::error::Error;
-use ron::de::from_reader;
-use std::{fs::File, fs};
+use lib::encoding::*;
+use lib::save::*;
+use std::fs;
+use std::path::Path;
+use std::collections::HashMap;
+use rand::prelude::*;
+
+// a menu that asks the player to choose an item
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Menu {
+    Inventory,
+    Drop,
+    Equip,
+    Unequip,
+    Eat,
+    Drink,
+}
+
+// saves are big, and no one's meant to read them, so they're
+// compressed (mv can convert them to RON for debugging)
+pub const SAVE_ENCODING: Encoding = Encoding::Compressed;
+
+// where the save for a map is kept
+fn save_path_for(map_path: &str) -> String {
+    format!("{}.save", map_path)
+}
+
+// how many messages page up and page down scroll
+// the message log by
+const SCROLLBACK_PAGE: usize = 10;
+
+// what the player is moving the cursor around to choose
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CursorMode {
+    Travel,
+    Look,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub mode: CursorMode,
+    pub coords: (usize, usize),
+}
 
 // this contains the entire state of the game
 pub struct State {
     pub dungeon: Dungeon,
-    pub messages: Vec<Message>,
     pub map_path: String,
+    pub scheduler: Scheduler,
+    pub menu: Option<Menu>,
+    pub cursor: Option<Cursor>,
+    pub autotravel: Option<AutoTravel>,
+
+    // how many messages back the player has scrolled,
+    // while the message log is open
+    pub scrollback: Option<usize>,
+
+    // how the player died, once they have
+    pub ending: Option<String>,
+
+    // whether the keybinding help is being shown
+    pub help: bool,
+
+    // the turn the game was last autosaved on
+    pub last_autosave: u64,
+
+    // everything the player's done, so that the
+    // game can be replayed
+    pub replay: Option<Replay>,
 }
 
 impl State {
-    pub fn from_file(path: &str) -> Result<State, Box<dyn Error>> {
-        let fmap = File::open(path.to_string())?;
-        let dungeon: Dungeon = from_reader(fmap)?;
+    // start playing a map, or carry on from where the player left
+    // off if there's a save for it (or the autosave, if restoring)
+    pub fn from_file(path: &str, restore: bool) -> Result<State, Box<dyn Error>> {
+        let save_path = save_path_for(path);
+        let data = if restore {
+            fs::read(autosave_path_for(path))?
+        } else if Path::new(&save_path).exists() {
+            fs::read(&save_path)?
+        } else {
+            fs::read(path)?
+        };
+        let mut dungeon = SaveFile::from_bytes(&data)?.dungeon;
+
+        dungeon.messages.push(Message::new(
+            "Welcome to Thangorodrim Heights: Crown Jewels of Angband",
+            Priority::Max));
+        dungeon.messages.push(Message::new(
+            "See the nonexistant manpage for gameplay details.",
+            Priority::Max));
+
+        Ok(State::new(dungeon, path))
+    }
 
-        Ok(State {
+    pub fn new(dungeon: Dungeon, path: &str) -> State {
+        let turn = dungeon.turn();
+        State {
+            scheduler: Scheduler::from_dungeon(&dungeon),
             dungeon: dungeon,
-            messages: vec![
-                Message::new("Welcome to Thangorodrim Heights: Crown Jewels of Angband",
-                    Priority::Max),
-                Message::new("See the nonexistant manpage for gameplay details.",
-                    Priority::Max),
-            ],
             map_path: path.to_string(),
-        })
+            menu: None,
+            cursor: None,
+            autotravel: None,
+            scrollback: None,
+            ending: None,
+            help: false,
+            last_autosave: turn,
+            replay: None,
+        }
+    }
+
+    pub fn save_path(&self) -> String {
+        save_path_for(&self.map_path)
     }
 
     pub fn save_to_file(&mut self) -> Result<(), Box<dyn Error>> {
-        fs::write(&self.map_path, ron::to_string(&self.dungeon)?.as_bytes())?;
-        self.messages.push(
+        let data = SaveFile::dungeon_to_bytes(&self.dungeon,
+            env!("CARGO_PKG_VERSION"), SAVE_ENCODING)?;
+        write_atomically(&self.save_path(), &data)?;
+
+        // the save's newer than any autosave now
+        self.clear_autosave();
+
+        self.dungeon.messages.push(
             Message::new(
-                &format!("Saved game to {}", &self.map_path), Priority::Normal));
+                &format!("Saved game to {}", self.save_path()), Priority::Normal));
         Ok(())
     }
 
-    pub fn handle_action(&mut self, ac: KeybindingAction) {
+    // dead players don't get to carry on
+    pub fn delete_save(&self) {
+        let _ = fs::remove_file(self.save_path());
+        self.clear_autosave();
+    }
+
+    // end the game. cause is something like "bled to death"
+    pub fn player_dies(&mut self, cause: &str) {
+        if self.ending.is_some() {
+            return;
+        }
+
+        self.dungeon.messages.push(Message::new("You die...", Priority::Max));
+        self.ending = Some(cause.to_string());
+    }
+
+    // returns what sort of action the player took, so that it
+    // can be scheduled accordingly, or None if no time passed
+    pub fn handle_action<R>(&mut self, ac: KeybindingAction,
+        mobs: &HashMap<String, MobTemplate>,
+        materials: &HashMap<String, MaterialInfo>, rng: &mut R) -> Option<ActionKind>
+    where
+        R: Rng
+    {
+        // doing anything at all wakes the player up
+        let player_id = self.dungeon.player_mob_id();
+        let player = self.dungeon.mobs.get_mut(&player_id).unwrap();
+        match player.current_mode {
+            MobMode::Sleep => player.current_mode = MobMode::Wander,
+            MobMode::Unconscious => {
+                self.dungeon.messages.push(Message::new("You are unconscious.", Priority::Normal));
+                return Some(ActionKind::Wait);
+            },
+            _ => (),
+        }
+
         match ac {
-            KeybindingAction::LevelUp => (),
-            KeybindingAction::LevelDown => (),
+            KeybindingAction::LevelUp => {
+                match self.dungeon.player.level.checked_sub(1) {
+                    Some(newlevel) => Some(self.change_level(newlevel)),
+                    None => Some(ActionKind::Wait),
+                }
+            },
+            KeybindingAction::LevelDown => {
+                let newlevel = self.dungeon.player.level + 1;
+                Some(self.change_level(newlevel))
+            },
             KeybindingAction::Move(d) => {
                 let level = self.dungeon.player.level;
                 let cur_pos = Coord::from(self.dungeon.player.coords);
@@ -51,20 +199,324 @@ impl State {
                     .clamp_x(self.dungeon.levels[level].width)
                     .clamp_y(self.dungeon.levels[level].height)
                     .as_yx();
-                if self.dungeon.at(level, new_pos).tiletype == TileType::Wall {
+                let target = self.dungeon.at(level, new_pos).mobs;
+                if let Some(victim_id) = target.filter(|id| *id != player_id) {
+                    // walking into a mob attacks it
+                    let mut ctx = Ctx {
+                        st: self,
+                        mobs: mobs,
+                        materials: materials,
+                        rng: rng,
+                    };
+                    attack(&mut ctx, level, player_id, cur_pos.as_yx(),
+                        victim_id, new_pos);
+                    Some(ActionKind::Attack)
+                } else if self.dungeon.at(level, new_pos).tiletype == TileType::Wall {
                     // impassable for heavens sake
                     // do nothing
                     // in the future, though, we'll check if the player
                     // has a pickaxe in his inventory and if so, demolish
                     // the wall
+                    Some(ActionKind::Wait)
                 } else {
                     self.dungeon.player.coords = new_pos;
                     self.dungeon.move_mob(level, cur_pos.as_yx(),
                         level, new_pos, true).unwrap();
+                    Some(ActionKind::Move)
                 }
             },
-            KeybindingAction::Wait => (),
-            _ => (),
+            KeybindingAction::Wait => Some(ActionKind::Wait),
+            KeybindingAction::PickUp => self.pick_up(),
+            KeybindingAction::Drop => self.open_menu(Menu::Drop),
+            KeybindingAction::Equip => self.open_menu(Menu::Equip),
+            KeybindingAction::Unequip => self.open_menu(Menu::Unequip),
+            KeybindingAction::Inventory => self.open_menu(Menu::Inventory),
+            KeybindingAction::Eat => self.open_menu(Menu::Eat),
+            KeybindingAction::Drink => self.open_menu(Menu::Drink),
+            KeybindingAction::Explore => {
+                self.start_travel(TravelGoal::Explore);
+                None
+            },
+            KeybindingAction::Travel => self.open_cursor(CursorMode::Travel),
+            KeybindingAction::Look => self.open_cursor(CursorMode::Look),
+            KeybindingAction::MessageLog => {
+                self.scrollback = Some(0);
+                None
+            },
+            KeybindingAction::Help => {
+                self.help = true;
+                None
+            },
+            KeybindingAction::Sleep => {
+                let player = self.dungeon.mobs.get_mut(&player_id).unwrap();
+                player.current_mode = MobMode::Sleep;
+                self.dungeon.messages.push(Message::new("You fall asleep.", Priority::Normal));
+                Some(ActionKind::Sleep)
+            },
+            _ => Some(ActionKind::Wait),
         }
     }
+
+    // handle a keypress while the player is moving the cursor
+    // around; the movement keys move it, enter chooses a tile
+    pub fn handle_cursor(&mut self, ev: KeyPress, action: Option<KeybindingAction>) {
+        let cursor = match self.cursor {
+            Some(c) => c,
+            None => return,
+        };
+
+        match ev.key {
+            Key::Escape => self.cursor = None,
+            Key::Enter => {
+                self.cursor = None;
+                match cursor.mode {
+                    CursorMode::Travel => self.start_travel(TravelGoal::To(cursor.coords)),
+                    CursorMode::Look => (),
+                }
+            },
+            _ => {
+                if let Some(KeybindingAction::Move(d)) = action {
+                    let level = &self.dungeon.levels[self.dungeon.player.level];
+                    let coords = Coord::from(cursor.coords).neighbor_in_direction(d)
+                        .clamp_x(level.width - 1)
+                        .clamp_y(level.height - 1)
+                        .as_yx();
+                    self.cursor = Some(Cursor { coords: coords, ..cursor });
+                }
+            },
+        }
+    }
+
+    // handle a keypress while the message log is open;
+    // the north and south movement keys scroll it
+    pub fn handle_scrollback(&mut self, ev: KeyPress, action: Option<KeybindingAction>) {
+        let offset = match self.scrollback {
+            Some(o) => o,
+            None => return,
+        };
+
+        let max = self.dungeon.messages.len().saturating_sub(1);
+        self.scrollback = match (ev.key, action) {
+            (Key::Escape, _) => None,
+            (Key::PageUp, _) =>
+                Some(std::cmp::min(offset + SCROLLBACK_PAGE, max)),
+            (Key::PageDown, _) =>
+                Some(offset.saturating_sub(SCROLLBACK_PAGE)),
+            (_, Some(KeybindingAction::Move(Direction::North))) =>
+                Some(std::cmp::min(offset + 1, max)),
+            (_, Some(KeybindingAction::Move(Direction::South))) =>
+                Some(offset.saturating_sub(1)),
+            _ => Some(offset),
+        };
+    }
+
+    // whether the map is on the screen, rather than
+    // a menu or the help or the message log
+    fn map_shown(&self) -> bool {
+        !self.help && self.scrollback.is_none() && self.menu.is_none()
+    }
+
+    // move the cursor to a tile the player clicked on,
+    // opening it in look mode if it wasn't already open
+    pub fn look_at(&mut self, coords: (usize, usize)) {
+        if !self.map_shown() {
+            return;
+        }
+
+        let mode = self.cursor.map(|c| c.mode).unwrap_or(CursorMode::Look);
+        self.cursor = Some(Cursor { mode: mode, coords: coords });
+    }
+
+    // set off for a tile the player double-clicked on
+    pub fn travel_to(&mut self, coords: (usize, usize)) {
+        if !self.map_shown() {
+            return;
+        }
+
+        self.cursor = None;
+        if coords != self.dungeon.player.coords {
+            self.start_travel(TravelGoal::To(coords));
+        }
+    }
+
+    fn open_cursor(&mut self, mode: CursorMode) -> Option<ActionKind> {
+        self.cursor = Some(Cursor {
+            mode: mode,
+            coords: self.dungeon.player.coords,
+        });
+        None
+    }
+
+    fn open_menu(&mut self, menu: Menu) -> Option<ActionKind> {
+        self.menu = Some(menu);
+        None
+    }
+
+    // handle a keypress while a menu is open
+    // items are chosen by letter, starting with 'a'
+    pub fn handle_menu(&mut self, ev: KeyPress,
+        materials: &HashMap<String, MaterialInfo>) -> Option<ActionKind>
+    {
+        let menu = self.menu?;
+        let ch = match ev.key {
+            Key::Char(c) => c,
+            Key::Escape => {
+                self.menu = None;
+                return None;
+            },
+            _ => return None,
+        };
+
+        self.menu = None;
+        if !ch.is_ascii_lowercase() {
+            return None;
+        }
+
+        let index = (ch as usize) - ('a' as usize);
+        let (y, x) = self.dungeon.player.coords;
+        let lvl = self.dungeon.player.level;
+        let player_id = self.dungeon.player_mob_id();
+        let player = self.dungeon.mobs.get_mut(&player_id).unwrap();
+
+        match menu {
+            Menu::Inventory => None,
+            Menu::Drop => {
+                if index >= player.inventory.len() {
+                    return None;
+                }
+
+                let item = player.inventory.remove(index);
+                self.dungeon.messages.push(Message::new(
+                    &format!("You drop the {}.", item.name), Priority::Normal));
+                self.dungeon.levels[lvl].d[y][x].items.push(item);
+                Some(ActionKind::Drop)
+            },
+            Menu::Equip => {
+                if index >= player.inventory.len() {
+                    return None;
+                }
+
+                match player.equip(index) {
+                    Ok(slot) => {
+                        self.dungeon.messages.push(Message::new(
+                            &format!("You put on the {}.", player.equipment[&slot].name),
+                            Priority::Normal));
+                        Some(ActionKind::Equip)
+                    },
+                    Err(e) => {
+                        self.dungeon.messages.push(Message::new(
+                            &format!("You {}.", e), Priority::Normal));
+                        None
+                    },
+                }
+            },
+            Menu::Unequip => {
+                let slots = player.equipment_slots().into_iter()
+                    .filter(|s| player.equipment.contains_key(s))
+                    .collect::<Vec<_>>();
+                let slot = *slots.get(index)?;
+                let item = player.unequip(slot).ok()?;
+                self.dungeon.messages.push(Message::new(
+                    &format!("You take off the {}.", item.name), Priority::Normal));
+                Some(ActionKind::Equip)
+            },
+            Menu::Eat => {
+                if index >= player.inventory.len() {
+                    return None;
+                }
+
+                if !player.inventory[index].is_edible(materials) {
+                    self.dungeon.messages.push(Message::new(
+                        &format!("You can't eat the {}.", player.inventory[index].name),
+                        Priority::Normal));
+                    return None;
+                }
+
+                let item = player.inventory.remove(index);
+                player.eat(&item);
+                self.dungeon.messages.push(Message::new(
+                    &format!("You eat the {}.", item.name), Priority::Normal));
+                Some(ActionKind::Eat)
+            },
+            Menu::Drink => {
+                if index >= player.inventory.len() {
+                    return None;
+                }
+
+                if !player.inventory[index].is_drinkable(materials) {
+                    self.dungeon.messages.push(Message::new(
+                        &format!("You can't drink the {}.", player.inventory[index].name),
+                        Priority::Normal));
+                    return None;
+                }
+
+                let item = player.inventory.remove(index);
+                player.drink(&item);
+                self.dungeon.messages.push(Message::new(
+                    &format!("You drink the {}.", item.name), Priority::Normal));
+                Some(ActionKind::Drink)
+            },
+        }
+    }
+
+    // pick up everything on the player's tile that
+    // isn't too heavy to carry
+    fn pick_up(&mut self) -> Option<ActionKind> {
+        let (y, x) = self.dungeon.player.coords;
+        let lvl = self.dungeon.player.level;
+        let player_id = self.dungeon.player_mob_id();
+
+        let items = std::mem::take(&mut self.dungeon.levels[lvl].d[y][x].items);
+        if items.is_empty() {
+            self.dungeon.messages.push(Message::new(
+                "There's nothing here to pick up.", Priority::Low));
+            return None;
+        }
+
+        let player = self.dungeon.mobs.get_mut(&player_id).unwrap();
+        for item in items {
+            if player.can_carry(&item) {
+                self.dungeon.messages.push(Message::new(
+                    &format!("You pick up the {}.", item.name), Priority::Normal));
+                player.inventory.push(item);
+            } else {
+                self.dungeon.messages.push(Message::new(
+                    &format!("The {} is too heavy to carry.", item.name),
+                    Priority::Normal));
+                self.dungeon.levels[lvl].d[y][x].items.push(item);
+            }
+        }
+
+        Some(ActionKind::PickUp)
+    }
+
+    // move the player to the same spot (or as close as possible)
+    // on another level
+    // TODO: stairs
+    fn change_level(&mut self, newlevel: usize) -> ActionKind {
+        let oldlevel = self.dungeon.player.level;
+        if newlevel >= self.dungeon.levels.len() || newlevel == oldlevel {
+            return ActionKind::Wait;
+        }
+
+        let old_pos = self.dungeon.player.coords;
+        let new_pos = match self.dungeon.levels[newlevel].nearest_free_floor(old_pos) {
+            Some(c) => c,
+            None => return ActionKind::Wait,
+        };
+
+        let player_id = self.dungeon.player_mob_id();
+        self.dungeon.move_mob(oldlevel, old_pos, newlevel, new_pos, false).unwrap();
+        self.dungeon.player.level = newlevel;
+        self.dungeon.player.coords = new_pos;
+        self.dungeon.player.stealth.reached_level(newlevel);
+        self.dungeon.mobs.get_mut(&player_id).unwrap()
+            .switch_memory(oldlevel, newlevel);
+
+        let verb = if newlevel > oldlevel { "descend" } else { "climb up" };
+        self.dungeon.messages.push(Message::new(
+            &format!("You {} to level {}.", verb, newlevel + 1), Priority::Normal));
+
+        ActionKind::Move
+    }
 }
@@ -0,0 +1,128 @@
+// how mobs notice the player
+//
+// every tick that an awake mob can see the player, it has a chance
+// to notice them, depending on how well lit the player is, how far
+// away they are, how much noise they're making, how focused the mob
+// is, and how agile the player is. every time it notices something,
+// the mob gets more suspicious, until it's sure the player is there.
+
+use lib::message::*;
+use crate::sched::*;
+use crate::tick::*;
+use lib::coord::*;
+use lib::math::*;
+use lib::mob::*;
+use lib::priority::*;
+use rand::prelude::*;
+
+// a mob with this much focus notices the player exactly as
+// often as the light, distance, and noise would suggest
+const NORMAL_FOCUS: usize = 6;
+
+// how much more suspicious a mob gets each time it notices
+// the player, and how much less each tick it doesn't
+const NOTICE_SUSPICION: u16 = 40;
+const SUSPICION_DECAY: u16 = 2;
+
+// how much noise (as a percentage) the player makes while
+// doing something
+fn action_noise(action: ActionKind) -> usize {
+    match action {
+        ActionKind::Move => 100,
+        ActionKind::Wait => 40,
+        ActionKind::PickUp | ActionKind::Drop => 60,
+        ActionKind::Equip => 80,
+        ActionKind::Eat | ActionKind::Drink => 60,
+        ActionKind::Sleep => 30,
+        ActionKind::Cast => 150,
+        ActionKind::Attack => 150,
+    }
+}
+
+// the chance out of 100 that a mob will notice the player
+// this tick, if it can see the player
+fn notice_chance(mob: &Mob, light: usize, distance: usize, radius: usize,
+    action: ActionKind, player_agility: usize) -> usize
+{
+    if distance > radius {
+        return 0;
+    }
+
+    let mut chance = light * (radius + 1 - distance) / (radius + 1);
+    chance = chance * action_noise(action) / 100;
+
+    // a suspicious mob is already looking around for the player,
+    // and a mob that's busy eating isn't looking at all
+    let mut focus = mob.current_focus();
+    if mob.awareness() == Awareness::Suspicious {
+        focus *= 2;
+    }
+    if mob.current_mode == MobMode::Eat || mob.current_mode == MobMode::Drink {
+        focus /= 2;
+    }
+    chance = chance * focus / NORMAL_FOCUS;
+
+    let agility = std::cmp::max(player_agility, 1);
+    chance = chance * NORMAL_AGILITY as usize / agility;
+
+    clamp(chance, 0, 100)
+}
+
+// let a mob (which can see, and is on the player's level)
+// try and notice the player, and react if it does
+//
+// light is how well lit the player's tile is, and radius is
+// how far the mob can see
+pub fn detect_player<R>(ctx: &mut Ctx<R>, mob_id: u64, template: &MobTemplate,
+    coords: (usize, usize), light: usize, radius: usize, player_action: ActionKind)
+where
+    R: Rng
+{
+    let st = &mut *ctx.st;
+    let player = st.dungeon.player;
+    let player_id = st.dungeon.player_mob_id();
+    let player_pos = Coord::from(player.coords);
+    let player_agility = st.dungeon.mobs[&player_id].current_agility();
+
+    let mob = st.dungeon.mobs.get_mut(&mob_id).unwrap();
+    let before = mob.awareness();
+    let sees_player = mob.fov.contains(&player_pos);
+
+    if sees_player {
+        let distance = Coord::from(coords).distance(&player_pos);
+        let chance = notice_chance(mob, light, distance, radius,
+            player_action, player_agility);
+
+        // once a mob knows where the player is, it
+        // doesn't lose track of them while it can see them
+        if before == Awareness::Alerted {
+            mob.set_suspicion(ALERTED_AT);
+        } else if ctx.rng.gen_range(0, 100) < chance {
+            mob.set_suspicion(mob.suspicion + NOTICE_SUSPICION);
+        }
+    } else {
+        mob.set_suspicion(mob.suspicion.saturating_sub(SUSPICION_DECAY));
+    }
+
+    let after = mob.awareness();
+
+    // only a mob that's sure the player is there
+    // knows where they are
+    if sees_player && after == Awareness::Alerted {
+        mob.sightings.insert(player_id, MobSighting {
+            level: player.level, coords: player_pos, ticks_ago: 0,
+        });
+    }
+
+    // a mob that was already alerted, and only lost sight of
+    // the player for a while, hasn't spotted them again
+    if before != Awareness::Alerted && after == Awareness::Alerted {
+        st.dungeon.player.stealth.spotted();
+
+        if player_sees(st, player.level, coords) {
+            st.dungeon.messages.push(Message::new(
+                &format!("The {} notices you!", template.short_name),
+                Priority::High));
+        }
+    }
+}
@@ -1,8 +1,19 @@
+use crate::combat::*;
+use crate::sched::*;
+use lib::message::*;
 use crate::state::*;
+use crate::stealth::*;
 use lib::coord::*;
+use lib::dirs::*;
 use rand::prelude::*;
 use lib::dun_s1::*;
+use lib::dun_s2::*;
+use lib::dungeon::*;
+use lib::items::*;
+use lib::material::*;
+use lib::math::*;
 use lib::mob::*;
+use lib::priority::*;
 use doryen_fov::{
     FovAlgorithm,
     FovRecursiveShadowCasting,
@@ -12,117 +23,779 @@ use std::collections::HashMap;
 
 const PLAYER_VIEW_RADIUS: usize = 5;
 const MAX_PLAYER_MEMORY: usize = 1024 * 1024 * 1024;
-const MOB_VISION: usize = 3;
+const MAX_MOB_MEMORY: usize = 4096;
 
-pub fn mobs_tick<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>, rng: &mut R)
+// how far a mob with two eyes can see in full light
+const MOB_VISION: usize = 6;
+
+// how many ticks a mob will remember where it
+// last saw another mob
+const MOB_SIGHTING_MEMORY: usize = 64;
+
+// how bright (as a percentage of full light) a tile is
+// when there's nothing glowing nearby
+const AMBIENT_LIGHT: usize = 70;
+
+// how far away a glowing material lights up tiles
+const LIGHT_RADIUS: usize = 3;
+
+// chance in 100 that a summoner will summon something
+// on any given turn, and how many minions it can have at once
+const SUMMON_CHANCE: usize = 10;
+const MAX_SUMMONED: usize = 3;
+
+// how far a minion will stray from its master
+const MINION_LEASH: usize = 4;
+
+// how many millilitres of blood a vampire drinks in one bite,
+// and how badly the bite bleeds afterwards
+const VAMPIRE_BITE: u32 = 300;
+const BITE_BLEEDING: u32 = 10;
+
+// the most steps a mob will wander while catching up
+// on the time its level wasn't being simulated
+const MAX_CATCHUP_STEPS: u64 = 32;
+
+// what a mob needs in order to act: the game (and with it the
+// dungeon and the player's stealth record), the templates, and the rng
+pub struct Ctx<'a, R> {
+    pub st: &'a mut State,
+    pub mobs: &'a HashMap<String, MobTemplate>,
+    pub materials: &'a HashMap<String, MaterialInfo>,
+    pub rng: &'a mut R,
+}
+
+// let every mob act until it's the player's turn again
+pub fn mobs_tick<R>(st: &mut State, player_action: ActionKind,
+    mobs: &HashMap<String, MobTemplate>,
+    materials: &HashMap<String, MaterialInfo>, rng: &mut R)
 where
     R: Rng
 {
-    for lvl in 0..st.dungeon.levels.len() {
-        for y in 0..st.dungeon.levels[lvl].height {
-            for x in 0..st.dungeon.levels[lvl].width {
-                if (y, x) == st.dungeon.player.coords {
-                    continue;
-                }
+    if st.ending.is_some() {
+        return;
+    }
 
-                let tile = st.dungeon.at(lvl, (y, x));
+    let player_id = st.dungeon.player_mob_id();
+    let cost = action_cost(&st.dungeon.mobs[&player_id], player_action);
+    st.scheduler.schedule(player_id, cost);
 
-                // is there a mob here
-                if let Some(mob_id) = tile.mobs {
-                    assert!(st.dungeon.mobs.contains_key(&mob_id));
-                    let mob = &st.dungeon.mobs[&mob_id].clone();
-                    mob_tick(st, mob, &mobs[&mob.from_mob_template], rng, lvl, x, y);
-                }
+    activate_levels(st, mobs, rng);
+
+    let start_time = st.scheduler.time;
+    let mut map = transparency_map(&st.dungeon.levels[st.dungeon.player.level]);
+    let (py, px) = st.dungeon.player.coords;
+    let player_light = light_level(&st.dungeon.levels[st.dungeon.player.level],
+        py, px, materials);
+
+    while let Some(mob_id) = st.scheduler.pop_next() {
+        if mob_id == player_id || st.ending.is_some() {
+            break;
+        }
+
+        // the mob doesn't exist anymore, don't reschedule it
+        let (lvl, (y, x)) = match st.dungeon.mob_position(mob_id) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        // the mob's level has gone to sleep, don't reschedule it
+        if !st.scheduler.active_levels.contains(&lvl) {
+            continue;
+        }
+
+        assert!(st.dungeon.mobs.contains_key(&mob_id));
+        let mob = &st.dungeon.mobs[&mob_id].clone();
+
+        let mut ctx = Ctx {
+            st: st,
+            mobs: mobs,
+            materials: materials,
+            rng: rng,
+        };
+
+        // only mobs on the player's level get to see anything
+        if lvl == ctx.st.dungeon.player.level && mob.is_awake() {
+            let light = light_level(&ctx.st.dungeon.levels[lvl],
+                y, x, materials);
+            let radius = mob_vision_radius(mob, light);
+            update_vision(&mut ctx.st.dungeon, &mut map, mob_id,
+                lvl, (y, x), radius, MAX_MOB_MEMORY);
+            detect_player(&mut ctx, mob_id, &mobs[&mob.from_mob_template],
+                (y, x), player_light, radius, player_action);
+        }
+
+        let (action, _) = mob_tick(&mut ctx, mob_id,
+            &mobs[&mob.from_mob_template], lvl, x, y);
+        st.scheduler.schedule(mob_id, action_cost(mob, action));
+    }
+
+    // once the player's dead, nothing else happens
+    if st.ending.is_some() {
+        return;
+    }
+
+    let turns = (st.scheduler.time / TURN) - (start_time / TURN);
+    if turns > 0 {
+        world_tick(st, mobs, turns, rng);
+    }
+
+    st.dungeon.time = st.scheduler.time;
+    for lvl in &st.scheduler.active_levels {
+        st.dungeon.levels[*lvl].last_simulated = st.dungeon.time;
+    }
+}
+
+// let the mobs on the active levels get hungrier, thirstier,
+// sleepier, and older as the turns go by
+fn world_tick<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>,
+    turns: u64, rng: &mut R)
+where
+    R: Rng
+{
+    let player_id = st.dungeon.player_mob_id();
+    let player = &st.dungeon.mobs[&player_id];
+    let before = (player.hunger, player.thirst, player.tiredness,
+        player.current_mode);
+
+    // go through the mobs in a fixed order, so that the
+    // game plays out the same way for the same seed
+    let mut ids = st.dungeon.mob_positions().keys().copied().collect::<Vec<u64>>();
+    ids.sort();
+
+    for mob_id in ids {
+        if st.ending.is_some() {
+            return;
+        }
+
+        // skip any mobs that died of old age along the way
+        let (lvl, coords) = match st.dungeon.mob_position(mob_id) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        if !st.scheduler.active_levels.contains(&lvl) {
+            continue;
+        }
+
+        time_passes(st, mobs, mob_id, lvl, coords, turns, rng);
+    }
+
+    if st.ending.is_some() {
+        return;
+    }
+
+    let player = &st.dungeon.mobs[&player_id];
+    let after = (player.hunger, player.thirst, player.tiredness,
+        player.current_mode);
+    let crossed = |old: u16, new: u16| old < NEED_URGENT && new >= NEED_URGENT;
+
+    if crossed(before.0, after.0) {
+        st.dungeon.messages.push(Message::new("You are getting hungry.", Priority::High));
+    }
+    if crossed(before.1, after.1) {
+        st.dungeon.messages.push(Message::new("You are getting thirsty.", Priority::High));
+    }
+    if crossed(before.2, after.2) {
+        st.dungeon.messages.push(Message::new("You are getting sleepy.", Priority::High));
+    }
+    match (before.3, after.3) {
+        (MobMode::Sleep, MobMode::Wander) => {
+            st.dungeon.messages.push(Message::new("You wake up.", Priority::Normal));
+        },
+        (MobMode::Unconscious, MobMode::Wander) => {
+            st.dungeon.messages.push(Message::new("You come to.", Priority::High));
+        },
+        (old, MobMode::Unconscious) if old != MobMode::Unconscious => {
+            st.dungeon.messages.push(Message::new(
+                "You faint from loss of blood!", Priority::VHigh));
+        },
+        _ => (),
+    }
+}
+
+// let a mob get hungrier, thirstier, sleepier and older, and
+// bleed from (or heal) its wounds, as the turns go by
+fn time_passes<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>,
+    mob_id: u64, lvl: usize, coords: (usize, usize), turns: u64, rng: &mut R)
+where
+    R: Rng
+{
+    let player_id = st.dungeon.player_mob_id();
+    let mob = st.dungeon.mobs.get_mut(&mob_id).unwrap();
+    let template = &mobs[&mob.from_mob_template];
+
+    mob.tick_needs(template, turns);
+    mob.tick_confusion(turns);
+    mob.tick_grudges(turns);
+    let bled_out = mob.tick_blood(template, turns);
+
+    // the player decides for themselves what to do
+    if mob_id != player_id {
+        mob.choose_mode(template);
+    }
+
+    if bled_out && mob_id == player_id {
+        st.player_dies("bled to death");
+        return;
+    } else if bled_out {
+        st.dungeon.kill_mob(lvl, coords, template);
+
+        if player_sees(st, lvl, coords) {
+            st.dungeon.messages.push(Message::new(
+                &format!("The {} bleeds to death.", template.short_name),
+                Priority::Normal));
+        }
+        return;
+    }
+
+    age_mob(st, mobs, mob_id, lvl, coords, turns, rng);
+}
+
+// put the mobs of any newly active levels in the scheduler,
+// after letting them catch up on the time they missed
+fn activate_levels<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>,
+    rng: &mut R)
+where
+    R: Rng
+{
+    let active = active_levels(&st.dungeon);
+    let player_id = st.dungeon.player_mob_id();
+
+    for lvl in &active {
+        if st.scheduler.active_levels.contains(lvl) {
+            continue;
+        }
+
+        catch_up_level(st, *lvl, mobs, rng);
+
+        let mob_ids = st.dungeon.mob_positions().iter()
+            .filter(|(id, (l, _))| *l == *lvl && **id != player_id)
+            .map(|(id, _)| *id)
+            .collect::<Vec<u64>>();
+
+        // the mobs may still be in the queue from the last
+        // time the level was active
+        st.scheduler.unschedule(&mob_ids);
+        for mob_id in mob_ids {
+            let mob = &st.dungeon.mobs[&mob_id];
+            let cost = action_cost(mob, ActionKind::Wait);
+            st.scheduler.schedule(mob_id, cost);
+        }
+    }
+
+    st.scheduler.active_levels = active;
+}
+
+// cheaply simulate whatever a level's mobs would have done
+// since the level was last active
+fn catch_up_level<R>(st: &mut State, lvl: usize,
+    mobs: &HashMap<String, MobTemplate>, rng: &mut R)
+where
+    R: Rng
+{
+    let now = st.scheduler.time;
+    let elapsed = now.saturating_sub(st.dungeon.levels[lvl].last_simulated);
+    let turns = elapsed / TURN;
+    let player_id = st.dungeon.player_mob_id();
+
+    // wander around a bit, getting hungry while doing so
+    // (in a fixed order, as in world_tick)
+    let steps = std::cmp::min(turns, MAX_CATCHUP_STEPS);
+    let mut positions = st.dungeon.mob_positions().into_iter()
+        .collect::<Vec<(u64, (usize, (usize, usize)))>>();
+    positions.sort();
+
+    for (mob_id, (l, (y, x))) in positions {
+        if l != lvl || mob_id == player_id {
+            continue;
+        }
+
+        let template = &mobs[&st.dungeon.mobs[&mob_id].from_mob_template];
+        let mut cur_pos = Coord::from((y, x));
+        for _ in 0..steps {
+            if rng.gen_range(0, 100) > template.movement.chance_of_movement() {
+                continue;
+            }
+
+            if let Some(new_pos) = try_move(st, lvl, cur_pos, rng.gen()) {
+                cur_pos = Coord::from(new_pos);
             }
         }
+
+        time_passes(st, mobs, mob_id, lvl, cur_pos.as_yx(), turns, rng);
     }
+
+    st.dungeon.levels[lvl].last_simulated = now;
 }
 
-pub fn mob_tick<R>(st: &mut State, _mob: &Mob, template: &MobTemplate,
-    rng: &mut R, lvl: usize, x: usize, y: usize)
+// let a mob grow older, and if it's old enough,
+// either die or mature into something else
+fn age_mob<R>(st: &mut State, mobs: &HashMap<String, MobTemplate>,
+    mob_id: u64, lvl: usize, coords: (usize, usize), turns: u64, rng: &mut R)
 where
     R: Rng
 {
-    if rng.gen_range(0, 100) > template.movement.chance_of_movement() {
-        return; // nope
+    let player_id = st.dungeon.player_mob_id();
+    let mob = st.dungeon.mobs.get_mut(&mob_id).unwrap();
+    if !mob.tick_age(turns) {
+        return;
+    }
+
+    if mob_id == player_id {
+        st.player_dies("died of old age");
+        return;
+    }
+
+    let template = &mobs[&mob.from_mob_template];
+    let seen = player_sees(st, lvl, coords);
+
+    let matured = template.matures_into.as_ref()
+        .and_then(|id| mobs.get(id));
+    match matured {
+        Some(adult) => {
+            let old = &st.dungeon.mobs[&mob_id];
+            let new = adult.mature(old, rng);
+            st.dungeon.mobs.insert(mob_id, new);
+
+            if seen {
+                st.dungeon.messages.push(Message::new(
+                    &format!("The {} matures into a {}.",
+                        template.short_name, adult.short_name),
+                    Priority::Normal));
+            }
+        },
+        None => {
+            st.dungeon.kill_mob(lvl, coords, template);
+
+            if seen {
+                st.dungeon.messages.push(Message::new(
+                    &format!("The {} dies of old age.", template.short_name),
+                    Priority::Normal));
+            }
+        },
     }
+}
 
+// returns the action the mob took, and where
+// the mob ended up after taking it
+pub fn mob_tick<R>(ctx: &mut Ctx<R>, mob_id: u64, template: &MobTemplate,
+    lvl: usize, x: usize, y: usize) -> (ActionKind, (usize, usize))
+where
+    R: Rng
+{
     let cur_pos = Coord::from((y, x));
 
+    match ctx.st.dungeon.mobs[&mob_id].current_mode {
+        MobMode::Sleep | MobMode::Unconscious => return (ActionKind::Wait, (y, x)),
+        MobMode::Eat | MobMode::Drink => {
+            if let Some(action) = consume(ctx.st, mob_id, template, ctx.mobs,
+                lvl, (y, x), ctx.materials) {
+                return (action, (y, x));
+            }
+
+            // nothing to eat or drink here; wander around
+            // and hope to find something
+        },
+        _ => (),
+    }
+
+    let cast = summon(ctx.st, mob_id, template, ctx.mobs, ctx.rng, lvl, (y, x))
+        || raise_dead(ctx.st, mob_id, template, ctx.mobs, ctx.rng, lvl);
+    if cast {
+        return (ActionKind::Cast, (y, x));
+    }
+
+    // fight anything nearby that the mob wants to fight, and go
+    // after anything it wants to fight that it knows the whereabouts of
+    match choose_target(ctx.st, mob_id, lvl, cur_pos) {
+        Some((target, sighting)) => {
+            ctx.st.dungeon.mobs.get_mut(&mob_id).unwrap().current_mode = MobMode::AttackMob;
+
+            let target_pos = sighting.coords.as_yx();
+            let there = ctx.st.dungeon.at(lvl, target_pos).mobs == Some(target);
+            if there && cur_pos.distance(&sighting.coords) == 1 {
+                attack(ctx, lvl, mob_id, (y, x), target, target_pos);
+                return (ActionKind::Attack, (y, x));
+            }
+
+            match cur_pos.direction_to(&sighting.coords) {
+                Some(d) => {
+                    if let Some(new_pos) = try_move(ctx.st, lvl, cur_pos, d) {
+                        return (ActionKind::Move, new_pos);
+                    }
+                },
+                None => {
+                    // we're where we last saw the target, and
+                    // it isn't here anymore. give up.
+                    ctx.st.dungeon.mobs.get_mut(&mob_id).unwrap()
+                        .sightings.remove(&target);
+                },
+            }
+            return (ActionKind::Wait, (y, x));
+        },
+        None => {
+            let mob = ctx.st.dungeon.mobs.get_mut(&mob_id).unwrap();
+            if mob.current_mode == MobMode::AttackMob {
+                mob.current_mode = MobMode::Wander;
+            }
+        },
+    }
+
+    // minions stay close to whoever summoned or raised them
+    if let Some(master) = ctx.st.dungeon.mobs[&mob_id].master {
+        if let Some(sighting) = ctx.st.dungeon.mobs[&mob_id].sightings.get(&master).copied() {
+            if sighting.level == lvl && cur_pos.distance(&sighting.coords) > MINION_LEASH {
+                let d = cur_pos.direction_to(&sighting.coords).unwrap();
+                if let Some(new_pos) = try_move(ctx.st, lvl, cur_pos, d) {
+                    return (ActionKind::Move, new_pos);
+                }
+            }
+        }
+    }
+
+    if ctx.rng.gen_range(0, 100) > template.movement.chance_of_movement() {
+        return (ActionKind::Wait, (y, x)); // nope
+    }
+
     // get random direction
-    let mut new_pos = cur_pos.as_yx();
     for _ in 0..8 {
-        new_pos = cur_pos.neighbor_in_direction(rng.gen())
-            .clamp_x(st.dungeon.levels[lvl].width)
-            .clamp_y(st.dungeon.levels[lvl].height)
-            .as_yx();
-        if st.dungeon.at(lvl, new_pos).tiletype != TileType::Wall {
-                break;
+        if let Some(new_pos) = try_move(ctx.st, lvl, cur_pos, ctx.rng.gen()) {
+            return (ActionKind::Move, new_pos);
+        }
+    }
+
+    (ActionKind::Wait, (y, x))
+}
+
+// pick the closest mob on this level that the mob knows
+// about and wants to attack
+fn choose_target(st: &State, mob_id: u64, lvl: usize, cur_pos: Coord)
+    -> Option<(u64, MobSighting)>
+{
+    let mob = &st.dungeon.mobs[&mob_id];
+    mob.sightings.iter()
+        .filter(|(_, s)| s.level == lvl)
+        .filter(|(id, _)| match st.dungeon.mobs.get(*id) {
+            Some(other) => mob.hostile_to(mob_id, other, **id),
+            None => false,
+        })
+        .min_by_key(|(id, s)| (cur_pos.distance(&s.coords), **id))
+        .map(|(id, s)| (*id, *s))
+}
+
+// can the player see what's happening at coords?
+pub fn player_sees(st: &State, lvl: usize, coords: (usize, usize)) -> bool {
+    lvl == st.dungeon.player.level &&
+        st.dungeon.mobs[&st.dungeon.player_mob_id()].fov
+            .contains(&Coord::from(coords))
+}
+
+// summoners call allies to their side when they've
+// just seen the player
+fn summon<R>(st: &mut State, mob_id: u64, template: &MobTemplate,
+    mobs: &HashMap<String, MobTemplate>, rng: &mut R, lvl: usize,
+    coords: (usize, usize)) -> bool
+where
+    R: Rng
+{
+    if !template.summoner || rng.gen_range(0, 100) >= SUMMON_CHANCE {
+        return false;
+    }
+
+    let player_id = st.dungeon.player_mob_id();
+    let alerted = match st.dungeon.mobs[&mob_id].sightings.get(&player_id) {
+        Some(sighting) => sighting.ticks_ago == 0,
+        None => false,
+    };
+
+    if !alerted || st.dungeon.minions_of(mob_id).len() >= MAX_SUMMONED {
+        return false;
+    }
+
+    let mut summonable = mobs.values()
+        .filter(|t| t.summonable)
+        .collect::<Vec<&MobTemplate>>();
+    summonable.sort_by(|a, b| a.id.cmp(&b.id));
+    let summoned = match summonable.choose(rng) {
+        Some(t) => *t,
+        None => return false,
+    };
+
+    let minion = summoned.summon(rng, mob_id, &st.dungeon.mobs[&mob_id]);
+    let cost = action_cost(&minion, ActionKind::Wait);
+    let (minion_id, minion_coords) = match st.dungeon.spawn_mob(lvl, coords, minion) {
+        Some(m) => m,
+        None => return false,
+    };
+    st.scheduler.schedule(minion_id, cost);
+
+    if player_sees(st, lvl, coords) || player_sees(st, lvl, minion_coords) {
+        st.dungeon.messages.push(Message::new(
+            &format!("The {} summons a {}!", template.short_name, summoned.short_name),
+            Priority::High));
+    }
+
+    true
+}
+
+// necromancers raise any corpses they can see, until they
+// have as many undead minions as they can control
+fn raise_dead<R>(st: &mut State, mob_id: u64, template: &MobTemplate,
+    mobs: &HashMap<String, MobTemplate>, rng: &mut R, lvl: usize) -> bool
+where
+    R: Rng
+{
+    let necromancer = &st.dungeon.mobs[&mob_id];
+    let raised = st.dungeon.minions_of(mob_id).iter()
+        .filter(|id| st.dungeon.mobs[*id].undead)
+        .count();
+    if raised >= necromancer.max_raised as usize {
+        return false;
+    }
+
+    // find a corpse
+    let mut corpse = None;
+    for coord in &necromancer.fov {
+        let (y, x): (usize, usize) = coord.as_yx();
+        let items = &st.dungeon.levels[lvl].d[y][x].items;
+        let found = items.iter().position(|i| {
+            i.item_type == ItemType::Corpse && i.mob_template.as_ref()
+                .map(|t| mobs.contains_key(t)).unwrap_or(false)
+        });
+
+        if let Some(i) = found {
+            corpse = Some(((y, x), i));
+            break;
         }
     }
 
+    let (coords, index) = match corpse {
+        Some(c) => c,
+        None => return false,
+    };
+
+    let items = &mut st.dungeon.levels[lvl].d[coords.0][coords.1].items;
+    let corpse = items.remove(index);
+    let corpse_template = &mobs[corpse.mob_template.as_ref().unwrap()];
+    let undead = corpse_template.raise_undead(rng, mob_id, &st.dungeon.mobs[&mob_id]);
+    let cost = action_cost(&undead, ActionKind::Wait);
+
+    match st.dungeon.spawn_mob(lvl, coords, undead) {
+        Some((undead_id, _)) => st.scheduler.schedule(undead_id, cost),
+        None => {
+            // no room for it; put the corpse back
+            st.dungeon.levels[lvl].d[coords.0][coords.1].items.push(corpse);
+            return false;
+        },
+    }
+
+    if player_sees(st, lvl, coords) {
+        st.dungeon.messages.push(Message::new(
+            &format!("The {} raises the {}!", template.short_name, corpse.name),
+            Priority::High));
+    }
+
+    true
+}
+
+// eat or drink (depending on what the mob wants) something from
+// the mob's inventory or from the floor underneath it
+fn consume(st: &mut State, mob_id: u64, template: &MobTemplate,
+    mobs: &HashMap<String, MobTemplate>, lvl: usize, coords: (usize, usize),
+    materials: &HashMap<String, MaterialInfo>) -> Option<ActionKind>
+{
+    if template.vampire {
+        return drink_blood(st, mob_id, template, mobs, lvl, coords);
+    }
+
+    let mob = st.dungeon.mobs.get_mut(&mob_id).unwrap();
+    let drinking = mob.current_mode == MobMode::Drink;
+    let suitable = |item: &Item| {
+        if drinking { item.is_drinkable(materials) } else { item.is_edible(materials) }
+    };
+
+    let floor = &mut st.dungeon.levels[lvl].d[coords.0][coords.1].items;
+    let item = if let Some(i) = mob.inventory.iter().position(&suitable) {
+        mob.inventory.remove(i)
+    } else if let Some(i) = floor.iter().position(&suitable) {
+        floor.remove(i)
+    } else {
+        return None;
+    };
+
+    if drinking {
+        mob.drink(&item);
+        Some(ActionKind::Drink)
+    } else {
+        mob.eat(&item);
+        Some(ActionKind::Eat)
+    }
+}
+
+// bite a living mob next to the vampire and drink some of its blood,
+// sparing its master, its minions, and its allies
+fn drink_blood(st: &mut State, mob_id: u64, template: &MobTemplate,
+    mobs: &HashMap<String, MobTemplate>, lvl: usize, coords: (usize, usize))
+    -> Option<ActionKind>
+{
+    let level = &st.dungeon.levels[lvl];
+    let mob = &st.dungeon.mobs[&mob_id];
+    let cur_pos = Coord::from(coords);
+    let victim_id = Direction::all().iter()
+        .map(|d| cur_pos.neighbor_in_direction(*d)
+            .clamp_x(level.width - 1)
+            .clamp_y(level.height - 1)
+            .as_yx())
+        .filter(|pos: &(usize, usize)| *pos != coords)
+        .filter_map(|(y, x)| level.d[y][x].mobs)
+        .find(|id| {
+            let other = &st.dungeon.mobs[id];
+            !other.undead && other.blood() > 0 && mob.hostile_to(mob_id, other, *id)
+        })?;
+
+    let victim = st.dungeon.mobs.get_mut(&victim_id).unwrap();
+    let drunk = victim.lose_blood(VAMPIRE_BITE);
+    victim.wound(BITE_BLEEDING);
+    let victim_name = mobs[&victim.from_mob_template].short_name.clone();
+
+    st.dungeon.mobs.get_mut(&mob_id).unwrap().gain_blood(drunk);
+
+    if victim_id == st.dungeon.player_mob_id() {
+        st.dungeon.messages.push(Message::new(
+            &format!("The {} bites you!", template.short_name),
+            Priority::VHigh));
+    } else if player_sees(st, lvl, coords) {
+        st.dungeon.messages.push(Message::new(
+            &format!("The {} bites the {}.", template.short_name, victim_name),
+            Priority::Normal));
+    }
+
+    Some(ActionKind::Eat)
+}
+
+// move whatever's at cur_pos one tile in direction d,
+// if there's nothing in the way
+fn try_move(st: &mut State, lvl: usize, cur_pos: Coord, d: Direction)
+    -> Option<(usize, usize)>
+{
+    let new_pos = cur_pos.neighbor_in_direction(d)
+        .clamp_x(st.dungeon.levels[lvl].width - 1)
+        .clamp_y(st.dungeon.levels[lvl].height - 1)
+        .as_yx();
+
     if st.dungeon.at(lvl, new_pos).tiletype == TileType::Wall {
-            return;
+        return None;
     }
 
-    st.dungeon.move_mob(lvl, cur_pos.as_yx(), lvl, new_pos, true).unwrap();
+    st.dungeon.move_mob(lvl, cur_pos.as_yx(), lvl, new_pos, false).ok()?;
+    Some(new_pos)
 }
 
 pub fn player_tick(st: &mut State) {
     let player = st.dungeon.player;
-    let player_y = player.coords.0;
-    let player_x = player.coords.1;
-    let player_mob_id = st.dungeon.at(player.level, (player_y, player_x))
-        .mobs.unwrap();
-    let player_mob = st.dungeon.mobs.get_mut(&player_mob_id).unwrap();
+    let player_mob_id = st.dungeon.player_mob_id();
 
-    let map_width = st.dungeon.levels[player.level].width;
-    let map_height = st.dungeon.levels[player.level].height;
+    let mut map = transparency_map(&st.dungeon.levels[player.level]);
+    update_vision(&mut st.dungeon, &mut map, player_mob_id, player.level,
+        player.coords, PLAYER_VIEW_RADIUS, MAX_PLAYER_MEMORY);
+}
 
-    // update the FOV
-    let mut fov = FovRecursiveShadowCasting::new();
-    let mut map = MapData::new(map_width, map_height);
+// build a map of which tiles on a level can be seen through
+fn transparency_map(level: &DungeonS2) -> MapData {
+    let mut map = MapData::new(level.width, level.height);
 
-    let starty = 0;
-    let endy   = map_height;
-    let startx = 0;
-    let endx   = map_width;
+    for y in 0..level.height {
+        for x in 0..level.width {
+            let is_transparent = level.d[y][x].tiletype != TileType::Wall;
+            map.set_transparent(x, y, is_transparent);
+        }
+    }
 
-    for y in starty..endy {
-        for x in startx..endx {
-            let tile = st.dungeon.levels[player.level].d[y][x].tiletype;
+    map
+}
 
-            let is_transparent = if tile == TileType::Wall {
-                false
-            } else {
-                true
-            };
+// get the light level of a tile, as a percentage of full light
+fn light_level(level: &DungeonS2, y: usize, x: usize,
+    materials: &HashMap<String, MaterialInfo>) -> usize
+{
+    let mut emitted = 0;
 
-            map.set_transparent(x, y, is_transparent);
+    let endy = std::cmp::min(y + LIGHT_RADIUS + 1, level.height);
+    let endx = std::cmp::min(x + LIGHT_RADIUS + 1, level.width);
+    for ny in y.saturating_sub(LIGHT_RADIUS)..endy {
+        for nx in x.saturating_sub(LIGHT_RADIUS)..endx {
+            if let Some(mat) = materials.get(&level.d[ny][nx].tile_material) {
+                emitted = std::cmp::max(emitted, mat.emit_light);
+            }
         }
     }
 
+    // every ten lumens brightens the tile by one percent
+    clamp(AMBIENT_LIGHT + (emitted / 10), 0, 100)
+}
+
+// how far a mob can see, depending on how many eyes
+// it has and how well lit its surroundings are
+fn mob_vision_radius(mob: &Mob, light: usize) -> usize {
+    let eyes = mob.count_body_parts(MobBody::Eye);
+    if eyes == 0 {
+        return 0;
+    }
+
+    std::cmp::max(1, (MOB_VISION * eyes * light) / (2 * 100))
+}
+
+// recalculate what a mob can see, and update its memory
+// of the level and of the other mobs around it
+fn update_vision(dungeon: &mut Dungeon, map: &mut MapData, mob_id: u64,
+    lvl: usize, coords: (usize, usize), radius: usize, max_memory: usize)
+{
+    let player_id = dungeon.player_mob_id();
+    let level = &dungeon.levels[lvl];
+    let mob = dungeon.mobs.get_mut(&mob_id).unwrap();
+
+    // update the FOV
+    let mut fov = FovRecursiveShadowCasting::new();
     map.clear_fov();
-    fov.compute_fov(&mut map, player_x, player_y, PLAYER_VIEW_RADIUS, true);
+    fov.compute_fov(map, coords.1, coords.0, radius, true);
+
+    // forget old sightings
+    for sighting in mob.sightings.values_mut() {
+        sighting.ticks_ago += 1;
+    }
+    mob.sightings.retain(|_, s| s.ticks_ago < MOB_SIGHTING_MEMORY);
 
-    player_mob.fov = Vec::new();
-    for y in startx..endy {
-        for x in startx..endx {
+    mob.fov = Vec::new();
+    for y in 0..level.height {
+        for x in 0..level.width {
             if map.is_in_fov(x, y) {
                 let coord = Coord::from((y, x));
-                let tile = st.dungeon.levels[player.level].d[y][x].clone();
+                let tile = &level.d[y][x];
 
                 // add to field of vision
-                player_mob.fov.push(coord);
+                mob.fov.push(coord);
+
+                // remember where any other mobs are
+                // (except the player, who has to be noticed first;
+                // see stealth.rs)
+                if let Some(other) = tile.mobs {
+                    if other != mob_id && other != player_id {
+                        mob.sightings.insert(other, MobSighting {
+                            level: lvl, coords: coord, ticks_ago: 0,
+                        });
+                    }
+                }
 
                 // add to memory, if
                 // 1) this square hasn't been seen before
                 // 2) or it has been seen before, but the tile has changed
-                if !player_mob.memory.contains_key(&coord) ||
-                    player_mob.memory.get(&coord).unwrap() != &tile {
-                        if player_mob.memory.len() < MAX_PLAYER_MEMORY {
-                            player_mob.memory.insert(coord, tile);
-                        }
+                let changed = match mob.memory.get(&coord) {
+                    Some(remembered) => remembered != tile,
+                    None => true,
+                };
+                if changed && mob.memory.len() < max_memory {
+                    mob.memory.insert(coord, tile.clone());
                 }
             }
         }
@@ -1,5 +1,13 @@
-// TODO
-const PLAYER_TITLES: &[&str] = [
+use lib::dungeon::*;
+
+// the player's stealth score needed for each title
+const POINTS_PER_TITLE: u64 = 20;
+
+// how many turns a confused player sticks with one
+// delusion before moving on to the next
+const CONFUSED_TITLE_TURNS: u64 = 10;
+
+const PLAYER_TITLES: &[&str] = &[
     "Orc Fodder",
     "Greenhorn",
     "Unwelcome Visitor",
@@ -11,7 +19,7 @@ const PLAYER_TITLES: &[&str] = [
     "Phantom",
 ];
 
-const CONFUSED_PLAYER_TITLES: &[&str] = [
+const CONFUSED_PLAYER_TITLES: &[&str] = &[
     // it should be obvious what this is.
     "Befuddled",
     "Clueless",
@@ -40,3 +48,16 @@ const CONFUSED_PLAYER_TITLES: &[&str] = [
     // misc 3
     "Lord of the Blessed Realm",
 ];
+
+// the title the player has earned from how sneaky they've been,
+// or whatever they imagine they are if they're confused
+pub fn player_title(dungeon: &Dungeon) -> &'static str {
+    let player = &dungeon.mobs[&dungeon.player_mob_id()];
+    if player.confused > 0 {
+        let delusion = (dungeon.turn() / CONFUSED_TITLE_TURNS) as usize;
+        return CONFUSED_PLAYER_TITLES[delusion % CONFUSED_PLAYER_TITLES.len()];
+    }
+
+    let rank = (dungeon.player.stealth.score() / POINTS_PER_TITLE) as usize;
+    PLAYER_TITLES[std::cmp::min(rank, PLAYER_TITLES.len() - 1)]
+}
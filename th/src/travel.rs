@@ -0,0 +1,149 @@
+// walking the player somewhere automatically, one step at a time:
+// either exploring whatever they haven't seen yet, or travelling
+// to a tile they remember
+//
+// both only use what the player has seen, not the real level.
+
+use lib::message::*;
+use crate::state::*;
+use lib::coord::*;
+use lib::dirs::*;
+use lib::dun_s1::*;
+use lib::mob::*;
+use lib::path::*;
+use lib::priority::*;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TravelGoal {
+    // the nearest tile next to something unexplored
+    Explore,
+    To((usize, usize)),
+}
+
+pub struct AutoTravel {
+    pub goal: TravelGoal,
+
+    // the items the player could see when they set off,
+    // so that only new ones stop them
+    seen_items: HashSet<Coord>,
+}
+
+impl State {
+    pub fn start_travel(&mut self, goal: TravelGoal) {
+        self.autotravel = Some(AutoTravel {
+            goal: goal,
+            seen_items: visible_items(self),
+        });
+    }
+
+    fn stop_travel(&mut self, why: &str) {
+        self.autotravel = None;
+        self.dungeon.messages.push(Message::new(why, Priority::Normal));
+    }
+}
+
+// the tiles with items on them that the player can see
+fn visible_items(st: &State) -> HashSet<Coord> {
+    let level = &st.dungeon.levels[st.dungeon.player.level];
+    let player = &st.dungeon.mobs[&st.dungeon.player_mob_id()];
+    player.fov.iter()
+        .filter(|c| {
+            let (y, x): (usize, usize) = c.as_yx();
+            !level.d[y][x].items.is_empty()
+        })
+        .copied()
+        .collect()
+}
+
+// can the player see anything that wants to hurt them?
+fn sees_danger(st: &State) -> bool {
+    let level = &st.dungeon.levels[st.dungeon.player.level];
+    let player_id = st.dungeon.player_mob_id();
+    let player = &st.dungeon.mobs[&player_id];
+    player.fov.iter().any(|c| {
+        let (y, x): (usize, usize) = c.as_yx();
+        match level.d[y][x].mobs {
+            Some(id) if id != player_id => st.dungeon.mobs.get(&id)
+                .map(|m| m.hostile_to(id, player, player_id))
+                .unwrap_or(false),
+            _ => false,
+        }
+    })
+}
+
+// whether the player remembers a tile as something
+// they could walk through
+fn remembered_passable(player: &Mob, coords: (usize, usize)) -> bool {
+    match player.memory.get(&Coord::from(coords)) {
+        Some(tile) => tile.tiletype != TileType::Wall,
+        None => false,
+    }
+}
+
+// the direction of the player's next step, or None if they've
+// arrived, can't go any further, or have been interrupted
+pub fn next_step(st: &mut State) -> Option<Direction> {
+    let goal = st.autotravel.as_ref()?.goal;
+
+    if sees_danger(st) {
+        st.stop_travel("You see something dangerous, and stop.");
+        return None;
+    }
+
+    let items = visible_items(st);
+    let travel = st.autotravel.as_mut()?;
+    let new_items = items.iter().any(|c| !travel.seen_items.contains(c));
+    travel.seen_items.extend(items);
+    if new_items {
+        st.stop_travel("You see something interesting, and stop.");
+        return None;
+    }
+
+    let level = &st.dungeon.levels[st.dungeon.player.level];
+    let (width, height) = (level.width, level.height);
+    let start = st.dungeon.player.coords;
+    let player = &st.dungeon.mobs[&st.dungeon.player_mob_id()];
+
+    let path = match goal {
+        TravelGoal::Explore => find_path(width, height, start,
+            |c| remembered_passable(player, c),
+            |c| {
+                c != start && Direction::all().iter().any(|d| {
+                    let n = Coord::from(c).neighbor_in_direction(*d)
+                        .clamp_x(width - 1)
+                        .clamp_y(height - 1);
+                    !player.memory.contains_key(&n)
+                })
+            }),
+        TravelGoal::To(dest) => find_path(width, height, start,
+            |c| remembered_passable(player, c),
+            |c| c == dest),
+    };
+
+    let next = match path {
+        Some(path) if !path.is_empty() => path[0],
+        Some(_) => {
+            match goal {
+                TravelGoal::Explore => st.stop_travel("There's nothing left to explore here."),
+                TravelGoal::To(_) => st.autotravel = None,
+            }
+            return None;
+        },
+        None => {
+            match goal {
+                TravelGoal::Explore => st.stop_travel("There's nothing left to explore here."),
+                TravelGoal::To(_) => st.stop_travel("You don't know how to get there."),
+            }
+            return None;
+        },
+    };
+
+    // don't bump into (and so attack) anything that's in the way
+    if level.d[next.0][next.1].mobs.is_some() {
+        st.stop_travel("There's something in the way.");
+        return None;
+    }
+
+    Coord::from(start).direction_to(&Coord::from(next))
+}
@@ -0,0 +1,376 @@
+// playing small, hand-built dungeons through a Game, and checking
+// that the player, their view, and the other mobs behave
+
+use lib::coord::*;
+use lib::dirs::*;
+use lib::dun_s1::*;
+use lib::dun_s2::*;
+use lib::dungeon::*;
+use lib::info_files::*;
+use lib::items::*;
+use lib::keys::*;
+use lib::material::*;
+use lib::mob::*;
+use rand::prelude::*;
+use std::collections::HashMap;
+use th::game::*;
+use th::input::*;
+use th::kbd::*;
+use th::replay::*;
+use th::sched::*;
+use th::state::*;
+use th::stealth::*;
+use th::tick::*;
+
+const SEED: u64 = 42;
+
+fn load() -> (HashMap<String, MobTemplate>, HashMap<String, MaterialInfo>) {
+    (load_info_files("../dat/mobs/").unwrap(), load_info_files("../dat/mats/").unwrap())
+}
+
+// two rooms on one level, split by a wall at x = 5:
+//
+//     ###########
+//     #....#....#
+//     #.@..#....#
+//     #....#....#
+//     ###########
+//
+// with the player at (2, 2)
+fn two_rooms(mobs: &HashMap<String, MobTemplate>) -> Dungeon {
+    // the player goes on the only floor tile there is, and
+    // the rest of the level is dug out around them
+    let mut s1 = DungeonS1::new(11, 5);
+    s1.set(2, 2, TileType::Floor);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut dungeon = Dungeon::from_dungeon_s2("test".to_string(),
+        &mut vec![DungeonS2::from_dungeon_s1(&s1)], &mut rng,
+        &mobs["elf"], HashMap::new());
+
+    for y in 1..4 {
+        for x in 1..10 {
+            if x != 5 {
+                dungeon.levels[0].d[y][x].tiletype = TileType::Floor;
+            }
+        }
+    }
+
+    assert_eq!(dungeon.player.coords, (2, 2));
+    dungeon
+}
+
+fn state(dungeon: Dungeon) -> State {
+    let path = std::env::temp_dir().join("th-test-map.ron");
+    State::new(dungeon, &path.to_string_lossy())
+}
+
+fn item(name: &str, item_type: ItemType, weight: usize) -> Item {
+    Item {
+        name: name.to_string(),
+        item_type: item_type,
+        material: "hematite".to_string(),
+        glyph: '(',
+        weight: weight,
+        mob_template: None,
+    }
+}
+
+// pick the item with the given letter from whichever menu is open
+fn choose(game: &mut Game, letter: char) {
+    game.input(Input::Key(KeyPress { key: Key::Char(letter), alt: false }, None));
+}
+
+#[test]
+fn player_moves_until_blocked_by_a_wall() {
+    let (mobs, materials) = load();
+    let mut game = Game::new(state(two_rooms(&mobs)), &mobs, &materials, SEED);
+    let player_id = game.dungeon().player_mob_id();
+
+    game.act(KeybindingAction::Move(Direction::East));
+    assert_eq!(game.dungeon().player.coords, (2, 3));
+    assert_eq!(game.dungeon().at(0, (2, 3)).mobs, Some(player_id));
+    assert_eq!(game.dungeon().at(0, (2, 2)).mobs, None);
+
+    game.act(KeybindingAction::Move(Direction::West));
+    game.act(KeybindingAction::Move(Direction::West));
+    assert_eq!(game.dungeon().player.coords, (2, 1));
+
+    // there's nothing to the west but wall
+    game.act(KeybindingAction::Move(Direction::West));
+    assert_eq!(game.dungeon().player.coords, (2, 1));
+}
+
+#[test]
+fn player_sees_their_room_but_not_through_walls() {
+    let (mobs, materials) = load();
+    let game = Game::new(state(two_rooms(&mobs)), &mobs, &materials, SEED);
+    let fov = &game.player().fov;
+
+    assert!(fov.contains(&Coord::from((2, 2))));
+    assert!(fov.contains(&Coord::from((1, 4))));
+    assert!(fov.contains(&Coord::from((2, 5))));
+    assert!(!fov.contains(&Coord::from((2, 6))));
+}
+
+#[test]
+fn fov_follows_the_player() {
+    let (mobs, materials) = load();
+    let mut game = Game::new(state(two_rooms(&mobs)), &mobs, &materials, SEED);
+
+    // knock a door through the wall and walk over to it
+    game.st.dungeon.levels[0].d[2][5].tiletype = TileType::Floor;
+    game.travel_to((2, 4));
+    assert_eq!(game.dungeon().player.coords, (2, 4));
+    assert!(game.player().fov.contains(&Coord::from((2, 8))));
+}
+
+#[test]
+fn mobs_take_turns_as_the_player_waits() {
+    let (mobs, materials) = load();
+    let mut dungeon = two_rooms(&mobs);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let elf = mobs["elf"].generate_mob(&mut rng);
+    let (elf_id, _) = dungeon.spawn_mob(0, (2, 8), elf).unwrap();
+
+    let mut game = Game::new(state(dungeon), &mobs, &materials, SEED);
+    assert!(game.dungeon().mobs[&elf_id].fov.is_empty());
+
+    game.wait(5);
+    assert_eq!(game.dungeon().turn(), 5);
+
+    // the elf has looked around its room, and is still in it
+    let elf = &game.dungeon().mobs[&elf_id];
+    assert!(!elf.fov.is_empty());
+    let (lvl, (_, x)) = game.dungeon().mob_position(elf_id).unwrap();
+    assert_eq!(lvl, 0);
+    assert!(x > 5);
+}
+
+#[test]
+fn player_picks_up_and_drops_items() {
+    let (mobs, materials) = load();
+    let mut dungeon = two_rooms(&mobs);
+    dungeon.levels[0].d[2][2].items = vec![
+        item("pickaxe", ItemType::DiggingTool, 2000),
+        item("helmet", ItemType::Headwear, 1000),
+    ];
+    let mut game = Game::new(state(dungeon), &mobs, &materials, SEED);
+
+    game.act(KeybindingAction::PickUp);
+    assert!(game.dungeon().at(0, (2, 2)).items.is_empty());
+    let names = game.player().inventory.iter()
+        .map(|i| i.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["pickaxe", "helmet"]);
+
+    game.act(KeybindingAction::Drop);
+    choose(&mut game, 'b');
+    assert_eq!(game.player().inventory.len(), 1);
+    assert_eq!(game.dungeon().at(0, (2, 2)).items[0].name, "helmet");
+
+    // there's nothing left to pick up after picking it all up
+    game.act(KeybindingAction::PickUp);
+    game.act(KeybindingAction::PickUp);
+    assert_eq!(game.dungeon().messages.all().last().unwrap().text,
+        "There's nothing here to pick up.");
+}
+
+#[test]
+fn player_equips_and_unequips_items() {
+    let (mobs, materials) = load();
+    let mut game = Game::new(state(two_rooms(&mobs)), &mobs, &materials, SEED);
+    let player_id = game.dungeon().player_mob_id();
+    game.st.dungeon.mobs.get_mut(&player_id).unwrap().inventory = vec![
+        item("pickaxe", ItemType::DiggingTool, 2000),
+        item("helmet", ItemType::Headwear, 1000),
+    ];
+
+    game.act(KeybindingAction::Equip);
+    choose(&mut game, 'b');
+    assert_eq!(game.player().equipment[&EquipmentSlot::Head].name, "helmet");
+    assert_eq!(game.player().inventory.len(), 1);
+
+    game.act(KeybindingAction::Equip);
+    choose(&mut game, 'a');
+    assert_eq!(game.player().equipment[&EquipmentSlot::Hand(0)].name, "pickaxe");
+    assert!(game.player().inventory.is_empty());
+
+    // the menu goes by the slots with something in them
+    game.act(KeybindingAction::Unequip);
+    choose(&mut game, 'a');
+    assert!(!game.player().equipment.contains_key(&EquipmentSlot::Hand(0)));
+    assert_eq!(game.player().inventory[0].name, "pickaxe");
+
+    // worn things still count towards what the player carries
+    assert_eq!(game.player().carried_weight(), 3000);
+}
+
+#[test]
+fn player_leaves_behind_what_they_cant_carry() {
+    let (mobs, materials) = load();
+    let mut dungeon = two_rooms(&mobs);
+    let player_id = dungeon.player_mob_id();
+    let max = dungeon.mobs[&player_id].max_carried_weight();
+    dungeon.levels[0].d[2][2].items = vec![
+        item("anvil", ItemType::Block, max + 1),
+        item("boulder", ItemType::Block, max - 100),
+        item("pebble", ItemType::Block, 100),
+        item("another pebble", ItemType::Block, 1),
+    ];
+    let mut game = Game::new(state(dungeon), &mobs, &materials, SEED);
+
+    game.act(KeybindingAction::PickUp);
+    let carried = game.player().inventory.iter()
+        .map(|i| i.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(carried, vec!["boulder", "pebble"]);
+    assert_eq!(game.player().carried_weight(), max);
+
+    let left = game.dungeon().levels[0].d[2][2].items.iter()
+        .map(|i| i.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(left, vec!["anvil", "another pebble"]);
+}
+
+#[test]
+fn agile_mobs_act_more_often() {
+    let (mobs, _) = load();
+    let mut dungeon = two_rooms(&mobs);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut ids = Vec::new();
+    for (agility, coords) in &[(NORMAL_AGILITY, (1, 7)), (NORMAL_AGILITY * 2, (3, 7))] {
+        let mut mob = mobs["elf"].generate_mob(&mut rng);
+        mob.max_agility = *agility as u8;
+        mob.agility = 100;
+        let (id, _) = dungeon.spawn_mob(0, *coords, mob).unwrap();
+        ids.push(id);
+    }
+
+    let mut scheduler = Scheduler::from_dungeon(&dungeon);
+    let start = scheduler.time;
+    for &id in &ids {
+        scheduler.schedule(id, 0);
+    }
+
+    // run the two of them for a hundred turns' worth of time
+    let mut actions = HashMap::new();
+    while let Some(id) = scheduler.pop_next() {
+        if scheduler.time >= start + TURN * 100 {
+            break;
+        }
+        *actions.entry(id).or_insert(0) += 1;
+        scheduler.schedule(id, action_cost(&dungeon.mobs[&id], ActionKind::Move));
+    }
+
+    assert_eq!(actions[&ids[0]], 100);
+    assert_eq!(actions[&ids[1]], 200);
+}
+
+#[test]
+fn mobs_only_spot_the_player_once_while_alerted() {
+    let (mobs, materials) = load();
+    let mut dungeon = two_rooms(&mobs);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let elf = mobs["elf"].generate_mob(&mut rng);
+    let (elf_id, coords) = dungeon.spawn_mob(0, (2, 4), elf).unwrap();
+    let player_pos = Coord::from(dungeon.player.coords);
+
+    let mut game = Game::new(state(dungeon), &mobs, &materials, SEED);
+    let st = &mut game.st;
+    let mut detect = |st: &mut State, sees_player: bool| {
+        st.dungeon.mobs.get_mut(&elf_id).unwrap().fov =
+            if sees_player { vec![player_pos] } else { vec![] };
+        let mut ctx = Ctx {
+            st: st,
+            mobs: &mobs,
+            materials: &materials,
+            rng: &mut rng,
+        };
+        detect_player(&mut ctx, elf_id, &mobs["elf"], coords, 100, 5,
+            ActionKind::Attack);
+    };
+
+    for _ in 0..100 {
+        detect(st, true);
+    }
+    assert_eq!(st.dungeon.mobs[&elf_id].awareness(), Awareness::Alerted);
+    assert_eq!(st.dungeon.player.stealth.times_spotted, 1);
+
+    // looking away for a moment isn't enough to lose track
+    detect(st, false);
+    assert_eq!(st.dungeon.mobs[&elf_id].awareness(), Awareness::Alerted);
+    detect(st, true);
+    assert_eq!(st.dungeon.player.stealth.times_spotted, 1);
+
+    let noticed = st.dungeon.messages.all().iter()
+        .filter(|m| m.text.contains("notices you"))
+        .map(|m| m.count)
+        .sum::<usize>();
+    assert_eq!(noticed, 1);
+}
+
+#[test]
+fn nothing_happens_after_the_player_dies() {
+    let (mobs, materials) = load();
+    let mut game = Game::new(state(two_rooms(&mobs)), &mobs, &materials, SEED);
+
+    // bleed to death, just as the player was getting hungry
+    let player_id = game.dungeon().player_mob_id();
+    let player = game.st.dungeon.mobs.get_mut(&player_id).unwrap();
+    player.blood_lost = player.max_blood() - 1;
+    player.bleeding = 100;
+    player.hunger = NEED_URGENT - 1;
+
+    game.act(KeybindingAction::Wait);
+    assert!(game.is_over());
+    assert_eq!(game.dungeon().messages.all().last().unwrap().text, "You die...");
+
+    game.wait(10);
+    assert_eq!(game.dungeon().messages.all().last().unwrap().text, "You die...");
+}
+
+#[test]
+fn recorded_games_replay_without_desyncs() {
+    let (mobs, materials) = load();
+    let mut dungeon = two_rooms(&mobs);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for (template, coords) in &[("elf", (1, 7)), ("internet_troll", (3, 9))] {
+        let mob = mobs[*template].generate_mob(&mut rng);
+        dungeon.spawn_mob(0, *coords, mob).unwrap();
+    }
+
+    let mut st = state(dungeon);
+    st.replay = Some(Replay::new(&st, SEED).unwrap());
+    let mut game = Game::new(st, &mobs, &materials, SEED);
+
+    game.st.dungeon.levels[0].d[2][5].tiletype = TileType::Floor;
+    game.act(KeybindingAction::Move(Direction::East));
+    game.act(KeybindingAction::Move(Direction::SouthEast));
+    game.wait(30);
+    game.travel_to((2, 7));
+    game.wait(30);
+
+    // the troll's in a fighting mood, and this
+    // should be the end of the player
+    assert!(game.is_over());
+
+    let replay = game.st.replay.clone().unwrap();
+    // long enough to have been checked along the way,
+    // and not just at the start
+    assert!(replay.checksums.len() > 1);
+
+    // the door was knocked through by hand, rather than by an
+    // input, so it has to be knocked through again
+    let mut replayed = Game::from_replay(&replay, &mobs, &materials).unwrap();
+    assert!(start_in_sync(&replay, replayed.dungeon()));
+    replayed.st.dungeon.levels[0].d[2][5].tiletype = TileType::Floor;
+
+    let mut playback = Playback::new(&replay);
+    while !playback.is_done() {
+        playback.step(&mut replayed);
+    }
+
+    assert!(playback.desyncs.is_empty(), "desyncs: {:?}", playback.desyncs);
+    assert_eq!(playback.handled(), replay.inputs.len());
+    assert_eq!(replayed.dungeon().checksum(), game.dungeon().checksum());
+}